@@ -5,18 +5,23 @@
 // src/main.rs
 // Use resvg's re-exported tiny-skia to avoid version conflicts
 use chrono::DateTime;
+use chrono::FixedOffset;
 use chrono::Local;
 use chrono::NaiveDateTime;
+use chrono::TimeZone;
 use chrono::Utc;
 use chrono::{Datelike, NaiveDate};
+use chrono_tz::Tz;
 use clap::Parser;
 use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::decompress_to_vec;
 use resvg::Tree as ResvgTree; // Also use resvg's re-exported usvg
 use resvg::tiny_skia;
 use resvg::usvg;
 use resvg::usvg::TreeParsing;
 use rusqlite::{Connection, Result, params};
 use serde::Deserialize;
+use serde::Serialize;
 use serde::de::DeserializeOwned;
 use serde_json::Value;
 use skia_safe::AlphaType;
@@ -27,11 +32,14 @@ use skia_safe::FontMgr;
 use skia_safe::Image;
 use skia_safe::ImageInfo;
 use skia_safe::Path;
+use skia_safe::Typeface;
 use skia_safe::gradient_shader;
 use skia_safe::image::CachingHint;
 use skia_safe::{
     Canvas, Color, Font, Paint, PaintStyle, Point, RRect, Rect, Surface, TextBlob, TileMode,
 };
+use std::cell::RefCell;
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
@@ -41,8 +49,15 @@ use std::hash::Hasher;
 use std::io;
 use std::io::BufWriter;
 use std::io::Write;
+use std::ops::Deref;
 use std::path::Path as FsPath;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::time::Instant;
+use std::time::SystemTime;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about)]
@@ -54,6 +69,568 @@ struct Args {
     /// Directory containing input JSON data
     #[arg(long, value_name = "DIR", default_value = ".")]
     data_dir: PathBuf,
+
+    /// Native panel width in pixels (overrides layout.json "display")
+    #[arg(long, value_name = "PX")]
+    width: Option<u32>,
+
+    /// Native panel height in pixels (overrides layout.json "display")
+    #[arg(long, value_name = "PX")]
+    height: Option<u32>,
+
+    /// Clockwise rotation from the rendered layout to the panel: 0, 90, 180 or 270
+    #[arg(long, value_name = "DEGREES")]
+    rotation: Option<Rotation>,
+
+    /// Lay out in landscape or portrait; picks a rotation if none is given
+    #[arg(long, value_enum)]
+    orientation: Option<Orientation>,
+
+    /// Packed frame format written to image.mz
+    #[arg(long, value_enum, default_value_t = OutputFormat::Gray3)]
+    output_format: OutputFormat,
+
+    /// Dithering used wherever a layout node doesn't pick its own
+    #[arg(long, value_enum, default_value_t = DitherMethod::FloydSteinberg)]
+    dither: DitherMethod,
+
+    /// Dithering used for text, which stays crisp when left undithered
+    #[arg(long, value_enum, default_value_t = DitherMethod::None)]
+    text_dither: DitherMethod,
+
+    /// Tile size in pixels for the partial-refresh delta (a multiple of 8)
+    #[arg(long, value_name = "PX", default_value_t = 32)]
+    delta_tile: usize,
+
+    /// Keep running, re-rendering on a schedule and when input data changes
+    #[arg(long)]
+    daemon: bool,
+
+    /// Seconds between scheduled renders in daemon mode
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    interval: u64,
+
+    /// Serve the latest frame over HTTP on this address, e.g. 0.0.0.0:8080
+    #[arg(long, value_name = "ADDR")]
+    serve: Option<String>,
+
+    /// Gamma applied to the gray image before dithering
+    #[arg(long, default_value_t = 1.6)]
+    gamma: f32,
+
+    /// JSON file listing display profiles; renders every one of them
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+
+    /// Render as if it were this wall-clock time, e.g. 2026-12-24T07:00
+    #[arg(long, value_name = "DATETIME", value_parser = parse_wall_clock)]
+    now: Option<NaiveDateTime>,
+
+    /// IANA timezone to render in, e.g. America/Chicago; defaults to the host's
+    #[arg(long, value_name = "ZONE")]
+    tz: Option<Tz>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// Fetch a data feed and write it to --data-dir in the envelope format
+    Fetch {
+        #[command(subcommand)]
+        feed: FetchFeed,
+    },
+
+    /// Check layout files against the panel resolution and report problems
+    ValidateLayout {
+        /// Layout files to check; defaults to every profile's layout
+        layouts: Vec<PathBuf>,
+    },
+
+    /// Re-render whenever the layout or data changes and show it in a browser
+    Preview(PreviewArgs),
+
+    /// Unpack an image.mz, using the image.json next to it, into a PNG
+    Decode {
+        /// Packed frame to decode
+        #[arg(default_value = "image.mz")]
+        input: PathBuf,
+
+        /// Where to write the decoded PNG
+        #[arg(long, short, default_value = "decoded.png")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum FetchFeed {
+    /// Forecast from Open-Meteo, written to weather.json
+    Weather(WeatherFetchArgs),
+
+    /// Weather (if configured) and every entry in the config's "feeds"
+    All,
+
+    /// One or more feeds from the config's "feeds", by name
+    #[command(external_subcommand)]
+    Named(Vec<String>),
+}
+
+#[derive(Debug, clap::Args)]
+struct WeatherFetchArgs {
+    /// Overrides "weather.latitude" from --config
+    #[arg(long, allow_negative_numbers = true)]
+    latitude: Option<f64>,
+
+    /// Overrides "weather.longitude" from --config
+    #[arg(long, allow_negative_numbers = true)]
+    longitude: Option<f64>,
+
+    /// Forecast endpoint, e.g. a local mock server when testing
+    #[arg(long, value_name = "URL", default_value = OPEN_METEO_URL)]
+    base_url: String,
+}
+
+#[derive(Debug, clap::Args)]
+struct PreviewArgs {
+    /// Address to serve the preview page on
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8090")]
+    addr: String,
+
+    /// Profile from --config to preview; defaults to the first
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Milliseconds between checks for changed files
+    #[arg(long, value_name = "MS", default_value_t = 500)]
+    poll_ms: u64,
+}
+
+/// ---- Display / panel geometry ----
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "u32")]
+enum Rotation {
+    R0,
+    R90,
+    R180,
+    R270,
+}
+
+impl Rotation {
+    fn swaps_axes(self) -> bool {
+        matches!(self, Rotation::R90 | Rotation::R270)
+    }
+
+    /// The rotation that undoes this one.
+    fn inverse(self) -> Self {
+        match self {
+            Rotation::R0 => Rotation::R0,
+            Rotation::R90 => Rotation::R270,
+            Rotation::R180 => Rotation::R180,
+            Rotation::R270 => Rotation::R90,
+        }
+    }
+}
+
+impl TryFrom<u32> for Rotation {
+    type Error = String;
+
+    fn try_from(degrees: u32) -> Result<Self, Self::Error> {
+        match degrees {
+            0 => Ok(Rotation::R0),
+            90 => Ok(Rotation::R90),
+            180 => Ok(Rotation::R180),
+            270 => Ok(Rotation::R270),
+            other => Err(format!(
+                "Invalid rotation '{}', expected 0, 90, 180 or 270",
+                other
+            )),
+        }
+    }
+}
+
+impl std::str::FromStr for Rotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let degrees = s
+            .trim()
+            .parse::<u32>()
+            .map_err(|_| format!("Invalid rotation '{}', expected 0, 90, 180 or 270", s))?;
+        Rotation::try_from(degrees)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+enum Orientation {
+    Landscape,
+    Portrait,
+}
+
+/// Optional top-level `"display"` object in layout.json.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+struct LayoutDisplay {
+    width: Option<u32>,
+    height: Option<u32>,
+    rotation: Option<Rotation>,
+    orientation: Option<Orientation>,
+}
+
+impl LayoutDisplay {
+    fn from_args(args: &Args) -> Self {
+        LayoutDisplay {
+            width: args.width,
+            height: args.height,
+            rotation: args.rotation,
+            orientation: args.orientation,
+        }
+    }
+
+    /// Fills in anything unset here from `fallback`.
+    fn or(self, fallback: LayoutDisplay) -> Self {
+        LayoutDisplay {
+            width: self.width.or(fallback.width),
+            height: self.height.or(fallback.height),
+            rotation: self.rotation.or(fallback.rotation),
+            orientation: self.orientation.or(fallback.orientation),
+        }
+    }
+}
+
+/// The non-node fields at the top of layout.json.
+#[derive(Debug, Default, Deserialize)]
+struct LayoutHeader {
+    #[serde(default)]
+    display: LayoutDisplay,
+}
+
+/// Resolved panel geometry: CLI flags (or profile settings) win over
+/// layout.json, which wins over defaults.
+#[derive(Debug, Clone, Copy)]
+struct PanelConfig {
+    /// Native panel size, i.e. the size of the packed frame
+    width: usize,
+    height: usize,
+    rotation: Rotation,
+}
+
+impl PanelConfig {
+    const DEFAULT_WIDTH: u32 = 1200;
+    const DEFAULT_HEIGHT: u32 = 825;
+
+    fn resolve(overrides: &LayoutDisplay, layout: &LayoutDisplay) -> Result<Self, String> {
+        let display = overrides.or(*layout);
+        let width = display.width.unwrap_or(Self::DEFAULT_WIDTH);
+        let height = display.height.unwrap_or(Self::DEFAULT_HEIGHT);
+
+        if width == 0 || height == 0 {
+            return Err(format!("Invalid panel size {}x{}", width, height));
+        }
+
+        let rotation = display.rotation;
+        let orientation = display.orientation;
+
+        let panel_landscape = width >= height;
+        let rotation = match (rotation, orientation) {
+            (Some(rotation), None) => rotation,
+            (None, None) => Rotation::R0,
+            (None, Some(orientation)) => {
+                if (orientation == Orientation::Landscape) == panel_landscape {
+                    Rotation::R0
+                } else {
+                    Rotation::R90
+                }
+            }
+            (Some(rotation), Some(orientation)) => {
+                let canvas_landscape = panel_landscape != rotation.swaps_axes();
+                if width != height && (orientation == Orientation::Landscape) != canvas_landscape {
+                    return Err(format!(
+                        "Orientation {:?} conflicts with rotation {:?} on a {}x{} panel",
+                        orientation, rotation, width, height
+                    ));
+                }
+                rotation
+            }
+        };
+
+        Ok(PanelConfig {
+            width: width as usize,
+            height: height as usize,
+            rotation,
+        })
+    }
+
+    /// Size of the canvas the layout is rendered into, before rotation.
+    fn canvas_size(&self) -> (usize, usize) {
+        if self.rotation.swaps_axes() {
+            (self.height, self.width)
+        } else {
+            (self.width, self.height)
+        }
+    }
+}
+
+/// ---- Profiles: one entry per physical display ----
+
+/// One entry in the `--config` file. Unset fields fall back to the CLI flags,
+/// and panel geometry then to the profile's own layout.json.
+#[derive(Debug, Deserialize)]
+struct ProfileConfig {
+    name: String,
+    #[serde(default = "default_layout_path")]
+    layout: PathBuf,
+    out_dir: PathBuf,
+    #[serde(flatten)]
+    display: LayoutDisplay,
+    output_format: Option<OutputFormat>,
+    gamma: Option<f32>,
+    dither: Option<DitherMethod>,
+    text_dither: Option<DitherMethod>,
+}
+
+fn default_layout_path() -> PathBuf {
+    PathBuf::from("layout.json")
+}
+
+/// The `--config` file: display profiles plus settings for the fetchers.
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    profiles: Vec<ProfileConfig>,
+    #[serde(default)]
+    weather: Option<WeatherLocation>,
+    #[serde(default)]
+    feeds: BTreeMap<String, FeedSource>,
+    #[serde(default)]
+    staleness: HashMap<String, StalenessPolicy>,
+}
+
+fn load_config(path: &FsPath) -> Result<ConfigFile, Box<dyn std::error::Error>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let config = serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+
+    Ok(config)
+}
+
+/// Everything needed to render and save one display's frame.
+#[derive(Debug)]
+struct Profile {
+    name: String,
+    root: LayoutNode,
+    panel: PanelConfig,
+    out_dir: PathBuf,
+    output_format: OutputFormat,
+    gamma: f32,
+    dither: DitherMethod,
+    text_dither: DitherMethod,
+}
+
+impl Profile {
+    /// The single profile described by the command line alone.
+    fn from_args(args: &Args) -> Result<Self, Box<dyn std::error::Error>> {
+        let (root, panel) =
+            load_layout(FsPath::new("layout.json"), &LayoutDisplay::from_args(args))?;
+
+        Ok(Profile {
+            name: "default".to_string(),
+            root: root,
+            panel: panel,
+            out_dir: args.out_dir.clone(),
+            output_format: args.output_format,
+            gamma: args.gamma,
+            dither: args.dither,
+            text_dither: args.text_dither,
+        })
+    }
+
+    /// Relative paths in the config file are taken from the file's directory.
+    fn from_config(
+        config: &ProfileConfig,
+        base_dir: &FsPath,
+        args: &Args,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let layout_path = base_dir.join(&config.layout);
+        let overrides = config.display.or(LayoutDisplay::from_args(args));
+        let (root, panel) = load_layout(&layout_path, &overrides)
+            .map_err(|e| format!("Profile '{}': {}", config.name, e))?;
+
+        Ok(Profile {
+            name: config.name.clone(),
+            root: root,
+            panel: panel,
+            out_dir: base_dir.join(&config.out_dir),
+            output_format: config.output_format.unwrap_or(args.output_format),
+            gamma: config.gamma.unwrap_or(args.gamma),
+            dither: config.dither.unwrap_or(args.dither),
+            text_dither: config.text_dither.unwrap_or(args.text_dither),
+        })
+    }
+}
+
+/// Profiles from the `--config` file if it lists any, otherwise one from the
+/// flags. A config with only feeds or staleness still drives a single panel.
+fn load_profiles(
+    args: &Args,
+    file: &ConfigFile,
+) -> Result<Vec<Profile>, Box<dyn std::error::Error>> {
+    let config_path = match &args.config {
+        Some(path) if !file.profiles.is_empty() => path,
+        _ => return Ok(vec![Profile::from_args(args)?]),
+    };
+
+    let base_dir = config_path.parent().unwrap_or(FsPath::new("."));
+    let mut profiles: Vec<Profile> = Vec::with_capacity(file.profiles.len());
+    for config in &file.profiles {
+        if profiles.iter().any(|p| p.name == config.name) {
+            return Err(format!("Duplicate profile name '{}'", config.name).into());
+        }
+        if profiles
+            .iter()
+            .any(|p| p.out_dir == base_dir.join(&config.out_dir))
+        {
+            return Err(format!(
+                "Profile '{}' shares its out_dir with another profile",
+                config.name
+            )
+            .into());
+        }
+        profiles.push(Profile::from_config(config, base_dir, args)?);
+    }
+
+    Ok(profiles)
+}
+
+/// Renders every profile from the same data. One profile failing doesn't
+/// stop the others; the first error is returned once they've all had a go.
+fn render_profiles(
+    profiles: &[Profile],
+    delta_tile: usize,
+    font_boss: &FontBoss,
+    data: &mut AllData,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut first_error = None;
+
+    for profile in profiles {
+        data.battery_profile = profile.name.clone();
+        println!(
+            "Rendering profile '{}': panel {}x{}, rotated {:?}, {:?} output",
+            profile.name,
+            profile.panel.width,
+            profile.panel.height,
+            profile.panel.rotation,
+            profile.output_format
+        );
+
+        if let Err(e) = render_and_save(profile, delta_tile, font_boss, data) {
+            eprintln!("Profile '{}' failed: {}", profile.name, e);
+            first_error.get_or_insert(format!("Profile '{}': {}", profile.name, e));
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(e.into()),
+        None => Ok(()),
+    }
+}
+
+/// Rotates a one-value-per-pixel buffer clockwise, returning the new buffer.
+fn rotate_buffer<T: Copy>(gray: &[T], width: usize, height: usize, rotation: Rotation) -> Vec<T> {
+    let mut out = gray.to_vec();
+
+    match rotation {
+        Rotation::R0 => {}
+        Rotation::R90 => {
+            // Output is height wide and width tall
+            for dy in 0..width {
+                for dx in 0..height {
+                    out[dy * height + dx] = gray[(height - 1 - dx) * width + dy];
+                }
+            }
+        }
+        Rotation::R180 => {
+            for (i, &val) in gray.iter().enumerate() {
+                out[gray.len() - 1 - i] = val;
+            }
+        }
+        Rotation::R270 => {
+            for dy in 0..width {
+                for dx in 0..height {
+                    out[dy * height + dx] = gray[dx * width + (width - 1 - dy)];
+                }
+            }
+        }
+    }
+
+    out
+}
+
+/// ---- Render clock: the moment a render is for ----
+
+/// "2026-12-24T07:00", with optional seconds and a space allowed for the T.
+fn parse_wall_clock(s: &str) -> Result<NaiveDateTime, String> {
+    [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(s.trim(), format).ok())
+    .ok_or_else(|| format!("Invalid time '{}', expected e.g. 2026-12-24T07:00", s))
+}
+
+/// Where renders get "now" from: the real time, or the `--now` wall-clock
+/// time, read in the `--tz` zone or the host's.
+#[derive(Debug, Clone, Copy)]
+struct RenderClock {
+    pinned: Option<DateTime<FixedOffset>>,
+    tz: Option<Tz>,
+}
+
+impl RenderClock {
+    fn from_args(args: &Args) -> Result<Self, String> {
+        let pinned = match args.now {
+            Some(now) => {
+                let resolved = match args.tz {
+                    Some(tz) => tz
+                        .from_local_datetime(&now)
+                        .earliest()
+                        .map(|t| t.fixed_offset()),
+                    None => Local
+                        .from_local_datetime(&now)
+                        .earliest()
+                        .map(|t| t.fixed_offset()),
+                };
+                // Only a time skipped by a DST change has no instant at all
+                Some(resolved.ok_or_else(|| {
+                    format!(
+                        "{} doesn't exist in {}",
+                        now,
+                        args.tz
+                            .map_or("the local timezone".to_string(), |tz| tz.to_string())
+                    )
+                })?)
+            }
+            None => None,
+        };
+
+        Ok(RenderClock {
+            pinned: pinned,
+            tz: args.tz,
+        })
+    }
+
+    fn now(&self) -> DateTime<FixedOffset> {
+        match (self.pinned, self.tz) {
+            (Some(pinned), _) => pinned,
+            (None, Some(tz)) => Utc::now().with_timezone(&tz).fixed_offset(),
+            (None, None) => Local::now().fixed_offset(),
+        }
+    }
 }
 
 /// ---- Data model (from JSON) ----
@@ -96,14 +673,14 @@ struct DailyScore {
     entries: Vec<Entry>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct LastGood {
     pub fetched_at: DateTime<Utc>,
     pub expires: Option<DateTime<Utc>>,
     pub data: Value,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct StateEnvelope {
     pub status: String,
     pub fetched_at: DateTime<Utc>,
@@ -125,6 +702,38 @@ struct SignificantDate {
     emoji: String,
 }
 
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+enum TodoPriority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+#[derive(Debug, Deserialize)]
+struct TodoItem {
+    title: String,
+    #[serde(default)]
+    done: bool,
+    due: Option<String>, // "%Y-%m-%d", like the other feeds
+    #[serde(default)]
+    priority: TodoPriority,
+    person_id: Option<u32>,
+}
+
+/// Posted by the e-ink device itself every time it wakes up.
+#[derive(Debug, Serialize, Deserialize)]
+struct BatteryReport {
+    percent: f64,
+    #[serde(default)]
+    charging: bool,
+    voltage: Option<f64>,
+    reported_at: DateTime<Utc>,
+    #[serde(default)]
+    firmware: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct WeatherResponse {
     pub latitude: f64,
@@ -171,22 +780,134 @@ pub struct DailyWeather {
     pub precipitation_probability: Vec<f64>,
 }
 
+/// A feed that loaded, along with how stale it is.
+pub struct Loaded<T> {
+    data: T,
+    status: FeedStatus,
+}
+
+/// Each feed loads on its own; the error is kept for the widget's placeholder.
+type FeedResult<T> = Result<Loaded<T>, String>;
+
 pub struct AllData {
-    weather: WeatherResponse,
-    weather_age_hours: f64,
-    significant_dates: Vec<SignificantDate>,
+    weather: FeedResult<WeatherResponse>,
+    significant_dates: Result<Vec<SignificantDate>, String>,
+
+    cleaning: FeedResult<Vec<DailyScore>>,
+    names: FeedResult<Vec<PersonName>>,
+    balances: FeedResult<Vec<PersonBalance>>,
+    upcoming_payouts: FeedResult<Vec<UpcomingPayout>>,
+    todo: FeedResult<Vec<TodoItem>>,
+
+    // Each panel's own report, keyed by profile name, with "" for the shared
+    // battery.json. A device may not have reported in yet.
+    batteries: HashMap<String, Loaded<BatteryReport>>,
+    /// The profile being rendered, whose battery the widgets show
+    battery_profile: String,
+
+    /// The moment being rendered. Widgets and feed ages all read the time
+    /// from here, so a render can be pinned to any instant.
+    now: DateTime<FixedOffset>,
+}
+
+impl AllData {
+    /// Every feed the renderer reads, by the names widgets and `when`
+    /// clauses use.
+    const FEEDS: [&'static str; 8] = [
+        "weather",
+        "dates",
+        "cleaning",
+        "names",
+        "balances",
+        "upcoming_payouts",
+        "todo",
+        "battery",
+    ];
+
+    /// The forecast's IANA zone, if the weather feed loaded with a known one.
+    fn weather_tz(&self) -> Option<Tz> {
+        let weather = &self.weather.as_ref().ok()?.data;
+        match weather.timezone.parse::<Tz>() {
+            Ok(tz) => Some(tz),
+            Err(_) => {
+                println!("Unknown weather timezone '{}'", weather.timezone);
+                None
+            }
+        }
+    }
+
+    /// The render time as a wall-clock time in `zone`.
+    fn local_now(&self, zone: ZoneSource) -> NaiveDateTime {
+        let tz = match zone {
+            ZoneSource::Render => None,
+            ZoneSource::Weather => self.weather_tz(),
+        };
+
+        match tz {
+            Some(tz) => self.now.with_timezone(&tz).naive_local(),
+            None => self.now.naive_local(),
+        }
+    }
+
+    /// The report from the panel being rendered, or else the shared one.
+    fn battery(&self) -> Option<&Loaded<BatteryReport>> {
+        self.batteries
+            .get(&self.battery_profile)
+            .or_else(|| self.batteries.get(""))
+    }
+
+    fn feed_status(&self, feed: &str) -> Option<FeedStatus> {
+        fn status<T>(feed: &FeedResult<T>) -> Option<FeedStatus> {
+            feed.as_ref().ok().map(|loaded| loaded.status)
+        }
+
+        match feed {
+            "weather" => status(&self.weather),
+            "cleaning" => status(&self.cleaning),
+            "names" => status(&self.names),
+            "balances" => status(&self.balances),
+            "upcoming_payouts" => status(&self.upcoming_payouts),
+            "todo" => status(&self.todo),
+            "battery" => self.battery().map(|loaded| loaded.status),
+            _ => None,
+        }
+    }
 
-    cleaning: Vec<DailyScore>,
-    cleaning_age_hours: f64,
+    fn feed_error(&self, feed: &str) -> Option<&str> {
+        fn error<T>(feed: &Result<T, String>) -> Option<&str> {
+            feed.as_ref().err().map(|e| e.as_str())
+        }
 
-    names: Vec<PersonName>,
-    names_age_hours: f64,
+        match feed {
+            "weather" => error(&self.weather),
+            "dates" => error(&self.significant_dates),
+            "cleaning" => error(&self.cleaning),
+            "names" => error(&self.names),
+            "balances" => error(&self.balances),
+            "upcoming_payouts" => error(&self.upcoming_payouts),
+            "todo" => error(&self.todo),
+            _ => None,
+        }
+    }
 
-    balances: Vec<PersonBalance>,
-    balances_age_hours: f64,
+    /// The first of the given feeds that failed to load, and why.
+    fn first_error<'a>(&'a self, feeds: &[&'a str]) -> Option<(&'a str, &'a str)> {
+        feeds
+            .iter()
+            .find_map(|feed| self.feed_error(feed).map(|e| (*feed, e)))
+    }
 
-    upcoming_payouts: Vec<UpcomingPayout>,
-    upcoming_payouts_age_hours: f64,
+    /// The stalest of the given feeds, which is what a widget shows.
+    fn worst_status(&self, feeds: &[&str]) -> Option<FeedStatus> {
+        feeds
+            .iter()
+            .filter_map(|feed| self.feed_status(feed))
+            .max_by(|a, b| {
+                a.staleness
+                    .cmp(&b.staleness)
+                    .then(a.age_hours.total_cmp(&b.age_hours))
+            })
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -195,45 +916,102 @@ pub enum LayoutNode {
     #[serde(rename = "container")]
     Container(ContainerNode),
 
+    #[serde(rename = "grid")]
+    Grid(GridNode),
+
+    #[serde(rename = "overlay")]
+    Overlay(OverlayNode),
+
     #[serde(rename = "date")]
-    Date(SizedNode),
+    Date(SizedNode<DateOptions>),
 
     #[serde(rename = "todo")]
-    Todo(SizedNode),
+    Todo(SizedNode<TodoOptions>),
 
     #[serde(rename = "hline")]
-    HLine(SizedNode),
+    HLine(SizedNode<LineOptions>),
 
     #[serde(rename = "vline")]
-    VLine(SizedNode),
+    VLine(SizedNode<LineOptions>),
 
     #[serde(rename = "weather")]
-    Weather(SizedNode),
+    Weather(SizedNode<WeatherOptions>),
 
     #[serde(rename = "allowance")]
-    Allowance(SizedNode),
+    Allowance(SizedNode<AllowanceOptions>),
 
     #[serde(rename = "countdown")]
-    Countdown(SizedNode),
+    Countdown(SizedNode<CountdownOptions>),
 
     #[serde(rename = "battery")]
-    Battery(SizedNode),
+    Battery(SizedNode<BatteryOptions>),
 
     #[serde(rename = "verse")]
-    Verse(SizedNode),
+    Verse(SizedNode<VerseOptions>),
 }
 
-/// Nodes that *must* have a size.
+/// Leaf nodes. `size` is required inside a split container; grid and
+/// overlay children get their box from the parent and may leave it out.
+/// `options` holds the widget's own settings and may be partial.
 #[derive(Debug, Deserialize)]
-pub struct SizedNode {
+#[serde(bound(deserialize = "O: Deserialize<'de> + Default"))]
+pub struct SizedNode<O> {
+    #[serde(default)]
     pub size: Size,
+    #[serde(default)]
+    pub dither: Option<DitherMethod>,
+    #[serde(flatten)]
+    pub layout: BoxLayout,
+    #[serde(default)]
+    pub options: O,
 }
 
 /// Container nodes do have a size and children.
 #[derive(Debug, Deserialize)]
 pub struct ContainerNode {
+    #[serde(default)]
     pub size: Size,
+    #[serde(default)]
+    pub dither: Option<DitherMethod>,
+    #[serde(flatten)]
+    pub layout: BoxLayout,
     pub split: SplitDirection,
+    /// Where children sit along the split when they don't fill it
+    #[serde(default)]
+    pub align: Align,
+    pub entries: Vec<LayoutNode>,
+}
+
+/// Lays children out on row and column tracks. Tracks take the same sizes
+/// as split children; a child picks its cell with `cell` or flows into the
+/// next free one.
+#[derive(Debug, Deserialize)]
+pub struct GridNode {
+    #[serde(default)]
+    pub size: Size,
+    #[serde(default)]
+    pub dither: Option<DitherMethod>,
+    #[serde(flatten)]
+    pub layout: BoxLayout,
+    pub rows: Vec<Size>,
+    pub columns: Vec<Size>,
+    /// Pixels between neighbouring tracks
+    #[serde(default)]
+    pub gap: i32,
+    pub entries: Vec<LayoutNode>,
+}
+
+/// Stacks children in the same rectangle, drawn in order, so later
+/// entries land on top. A child fills the box unless it sets `width` or
+/// `height`, in which case `anchor` says where it sits.
+#[derive(Debug, Deserialize)]
+pub struct OverlayNode {
+    #[serde(default)]
+    pub size: Size,
+    #[serde(default)]
+    pub dither: Option<DitherMethod>,
+    #[serde(flatten)]
+    pub layout: BoxLayout,
     pub entries: Vec<LayoutNode>,
 }
 
@@ -244,17 +1022,185 @@ pub enum SplitDirection {
     Vertical,
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    #[default]
+    Start,
+    Center,
+    End,
+}
+
+/// Box-model and placement fields every node accepts. `size`, `min` and
+/// `max` are the node's outer size along its parent's split; margin and
+/// then padding are taken out of that to leave the content box the widget
+/// draws in. `cell` only means something inside a grid, and `anchor`,
+/// `width` and `height` only inside an overlay. A node whose `when`
+/// doesn't hold is left out before its parent hands out space.
+#[derive(Debug, Default, Deserialize)]
+pub struct BoxLayout {
+    #[serde(default)]
+    pub margin: Edges,
+    #[serde(default)]
+    pub padding: Edges,
+    pub min: Option<u32>,
+    pub max: Option<u32>,
+    pub cell: Option<GridCell>,
+    #[serde(default)]
+    pub anchor: Anchor,
+    pub width: Option<Size>,
+    pub height: Option<Size>,
+    pub when: Option<Condition>,
+}
+
+impl BoxLayout {
+    fn clamp(&self, size: f64) -> f64 {
+        let size = self.max.map_or(size, |max| size.min(max as f64));
+        self.min.map_or(size, |min| size.max(min as f64))
+    }
+}
+
+/// Zero-based grid position, optionally spanning several tracks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct GridCell {
+    pub row: usize,
+    pub column: usize,
+    #[serde(default = "default_span")]
+    pub row_span: usize,
+    #[serde(default = "default_span")]
+    pub column_span: usize,
+}
+
+fn default_span() -> usize {
+    1
+}
+
+/// Where an overlay child smaller than the overlay sits.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Anchor {
+    #[default]
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl Anchor {
+    /// Fractions of the spare width and height that go before the child.
+    fn offsets(self) -> (f64, f64) {
+        match self {
+            Anchor::TopLeft => (0.0, 0.0),
+            Anchor::Top => (0.5, 0.0),
+            Anchor::TopRight => (1.0, 0.0),
+            Anchor::Left => (0.0, 0.5),
+            Anchor::Center => (0.5, 0.5),
+            Anchor::Right => (1.0, 0.5),
+            Anchor::BottomLeft => (0.0, 1.0),
+            Anchor::Bottom => (0.5, 1.0),
+            Anchor::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+/// Per-side pixel insets. Accepts `8`, `[8, 16]` (vertical, horizontal),
+/// `[top, right, bottom, left]` or `{ "left": 8 }`. Negative margins let a
+/// node draw past its slot.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Edges {
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub left: i32,
+}
+
+impl Edges {
+    fn inset(&self, x: i32, y: i32, width: i32, height: i32) -> (i32, i32, i32, i32) {
+        (
+            x + self.left,
+            y + self.top,
+            width - self.left - self.right,
+            height - self.top - self.bottom,
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for Edges {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            All(i32),
+            List(Vec<i32>),
+            Sides {
+                #[serde(default)]
+                top: i32,
+                #[serde(default)]
+                right: i32,
+                #[serde(default)]
+                bottom: i32,
+                #[serde(default)]
+                left: i32,
+            },
+        }
+
+        let edges = |top, right, bottom, left| Edges {
+            top: top,
+            right: right,
+            bottom: bottom,
+            left: left,
+        };
+
+        match Repr::deserialize(deserializer)? {
+            Repr::All(v) => Ok(edges(v, v, v, v)),
+            Repr::List(list) => match list[..] {
+                [v] => Ok(edges(v, v, v, v)),
+                [vertical, horizontal] => Ok(edges(vertical, horizontal, vertical, horizontal)),
+                [top, right, bottom, left] => Ok(edges(top, right, bottom, left)),
+                _ => Err(serde::de::Error::custom(format!(
+                    "Expected 1, 2 or 4 edge values, got {}",
+                    list.len()
+                ))),
+            },
+            Repr::Sides {
+                top,
+                right,
+                bottom,
+                left,
+            } => Ok(edges(top, right, bottom, left)),
+        }
+    }
+}
+
 /// Strongly typed size.
 ///
 /// Input formats supported:
 ///  - "10px" -> Size::Px(10)
+///  - "30%"  -> Size::Percent(30.0), of the parent's content box
 ///  - "75u"  -> Size::Unit(75.0)
+///
+/// A node that leaves it out counts as "1u".
 #[derive(Debug)]
 pub enum Size {
     Px(u64),
+    Percent(f64),
     Unit(f64),
 }
 
+impl Default for Size {
+    fn default() -> Self {
+        Size::Unit(1.0)
+    }
+}
+
 impl<'de> Deserialize<'de> for Size {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -265,31 +1211,452 @@ impl<'de> Deserialize<'de> for Size {
             let value = px.trim().parse::<u64>().map_err(serde::de::Error::custom)?;
             return Ok(Size::Px(value));
         }
+        if let Some(percent) = s.strip_suffix('%') {
+            let value = percent
+                .trim()
+                .parse::<f64>()
+                .map_err(serde::de::Error::custom)?;
+            return Ok(Size::Percent(value));
+        }
         if let Some(u) = s.strip_suffix('u') {
             let value = u.trim().parse::<f64>().map_err(serde::de::Error::custom)?;
             return Ok(Size::Unit(value));
         }
         Err(serde::de::Error::custom(format!(
-            "Invalid size '{}', expected like '10px' or '75u'",
+            "Invalid size '{}', expected like '10px', '30%' or '75u'",
             s
         )))
     }
 }
 
-/// Deterministically maps a 64-bit integer to a row id between 0 and count-1
-fn seed_to_index(seed: u64, count: i64) -> i64 {
-    // Just modulo to wrap around row count
-    (seed % (count as u64)) as i64
-}
+/// ---- Widget options: per-node settings from layout.json ----
 
-fn get_verse_by_seed<P: AsRef<FsPath>>(db_path: P, seed: u64) -> Result<Option<(String, String)>> {
-    let conn = Connection::open(db_path)?;
+// Every field defaults to how the widget has always drawn, so `options`
+// only needs the settings being changed.
 
-    // Count total rows
-    let count: i64 = conn.query_row("SELECT COUNT(*) FROM verses", [], |row| row.get(0))?;
+/// A typeface and point size, e.g. `{ "face": "bold", "size": 30 }`.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FontOption {
+    #[serde(default)]
+    pub face: FontFace,
+    pub size: f32,
+}
 
-    if count == 0 {
-        return Ok(None);
+impl FontOption {
+    const fn regular(size: f32) -> Self {
+        FontOption {
+            face: FontFace::Regular,
+            size: size,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FontFace {
+    #[default]
+    Regular,
+    Italic,
+    Bold,
+    RobotoExtraBold,
+}
+
+/// The zone a widget reads the date in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ZoneSource {
+    /// The render's zone: --tz, or the host's
+    #[default]
+    Render,
+    /// The weather feed's zone, or the render's when there's no forecast
+    Weather,
+}
+
+/// Colors are `"#rrggbb"` or one of the names the panels can show.
+fn deserialize_color<'de, D>(deserializer: D) -> Result<Color, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    let color = match s.as_str() {
+        "black" => Some(Color::BLACK),
+        "white" => Some(Color::WHITE),
+        "red" => Some(Color::from_rgb(255, 0, 0)),
+        "gray" | "grey" => Some(Color::from_rgb(128, 128, 128)),
+        hex => hex
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .map(|rgb| Color::from_rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)),
+    };
+    color.ok_or_else(|| {
+        serde::de::Error::custom(format!(
+            "Invalid color '{}', expected like '#808080', 'black', 'white', 'red' or 'gray'",
+            s
+        ))
+    })
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DateOptions {
+    /// The weekday and year; the date itself uses the bold face at this size
+    pub font: FontOption,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub color: Color,
+    pub zone: ZoneSource,
+}
+
+impl Default for DateOptions {
+    fn default() -> Self {
+        DateOptions {
+            font: FontOption::regular(35.0),
+            color: Color::BLACK,
+            zone: ZoneSource::Render,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TodoOptions {
+    /// Item titles
+    pub font: FontOption,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub color: Color,
+    /// Done and low-priority items, assignees and the "+N more" row
+    #[serde(deserialize_with = "deserialize_color")]
+    pub muted_color: Color,
+    pub row_height: i32,
+}
+
+impl Default for TodoOptions {
+    fn default() -> Self {
+        TodoOptions {
+            font: FontOption::regular(25.0),
+            color: Color::BLACK,
+            muted_color: Color::from_rgb(128, 128, 128),
+            row_height: 30,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LineOptions {
+    #[serde(deserialize_with = "deserialize_color")]
+    pub color: Color,
+}
+
+impl Default for LineOptions {
+    fn default() -> Self {
+        LineOptions {
+            color: Color::from_rgb(200, 200, 200),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WeatherOptions {
+    /// Hours drawn on the temperature and precipitation curves
+    pub forecast_hours: usize,
+    /// Days in the daily forecast row, capped by what the feed has
+    pub daily_columns: usize,
+    /// "Feels like", humidity and weekday labels
+    pub font: FontOption,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub color: Color,
+}
+
+impl Default for WeatherOptions {
+    fn default() -> Self {
+        WeatherOptions {
+            forecast_hours: 23,
+            daily_columns: 7,
+            font: FontOption::regular(25.0),
+            color: Color::BLACK,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AllowanceOptions {
+    /// Cleaning score columns, capped by what the feed has
+    pub cleaning_days: usize,
+    /// Names and balances
+    pub font: FontOption,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub color: Color,
+    pub row_height: i32,
+}
+
+impl Default for AllowanceOptions {
+    fn default() -> Self {
+        AllowanceOptions {
+            cleaning_days: 5,
+            font: FontOption::regular(25.0),
+            color: Color::BLACK,
+            row_height: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CountdownOptions {
+    pub font: FontOption,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub color: Color,
+    pub row_spacing: i32,
+    /// Which day counts as today
+    pub zone: ZoneSource,
+}
+
+impl Default for CountdownOptions {
+    fn default() -> Self {
+        CountdownOptions {
+            font: FontOption::regular(25.0),
+            color: Color::BLACK,
+            row_spacing: 45,
+            zone: ZoneSource::Render,
+        }
+    }
+}
+
+/// The battery row, which can also carry the "Updated ..." text.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatteryOptions {
+    pub show_updated: bool,
+    pub low_percent: f64,
+}
+
+impl Default for BatteryOptions {
+    fn default() -> Self {
+        BatteryOptions {
+            show_updated: true,
+            low_percent: 15.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VerseOptions {
+    /// The largest size tried; the verse shrinks from here until it fits
+    pub font: FontOption,
+    #[serde(deserialize_with = "deserialize_color")]
+    pub color: Color,
+}
+
+impl Default for VerseOptions {
+    fn default() -> Self {
+        VerseOptions {
+            font: FontOption::regular(25.0),
+            color: Color::BLACK,
+        }
+    }
+}
+
+/// ---- Layout conditions: `when` clauses on nodes ----
+
+/// Every condition given must hold, e.g. mornings at the weekend are
+/// `{ "time": { "from": "05:00", "to": "11:00" }, "weekdays": ["sat", "sun"] }`.
+/// `any` holds when one of its clauses does (or when it's empty), and
+/// `not` flips a clause.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Condition {
+    /// Local time of day, `to` exclusive; wraps past midnight if `to` < `from`
+    pub time: Option<TimeRange>,
+    pub weekdays: Option<Vec<chrono::Weekday>>,
+    pub dates: Option<DateRange>,
+    /// A feed that's stale, too old or failed to load
+    pub stale: Option<String>,
+    pub weather: Option<WeatherCondition>,
+    #[serde(default)]
+    pub any: Vec<Condition>,
+    pub not: Option<Box<Condition>>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeRange {
+    pub from: chrono::NaiveTime,
+    pub to: chrono::NaiveTime,
+}
+
+/// Inclusive. Either both ends are full dates ("2026-12-01") or both are
+/// yearly ("12-01"), and a yearly range may wrap past New Year.
+#[derive(Debug, Clone, Copy)]
+pub enum DateRange {
+    Dates(NaiveDate, NaiveDate),
+    Yearly((u32, u32), (u32, u32)),
+}
+
+impl<'de> Deserialize<'de> for DateRange {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Repr {
+            from: String,
+            to: String,
+        }
+
+        fn yearly(s: &str) -> Option<(u32, u32)> {
+            let (month, day) = s.split_once('-')?;
+            let (month, day) = (month.parse().ok()?, day.parse().ok()?);
+            // 2000 is a leap year, so "02-29" is allowed
+            NaiveDate::from_ymd_opt(2000, month, day).map(|_| (month, day))
+        }
+
+        let repr = Repr::deserialize(deserializer)?;
+        let full = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok();
+        match (full(&repr.from), full(&repr.to)) {
+            (Some(from), Some(to)) => return Ok(DateRange::Dates(from, to)),
+            (None, None) => {}
+            _ => {
+                return Err(serde::de::Error::custom(
+                    "Date range ends must both be YYYY-MM-DD or both be MM-DD",
+                ));
+            }
+        }
+        match (yearly(&repr.from), yearly(&repr.to)) {
+            (Some(from), Some(to)) => Ok(DateRange::Yearly(from, to)),
+            _ => Err(serde::de::Error::custom(format!(
+                "Invalid date range '{}' to '{}', expected like '2026-12-01' or '12-01'",
+                repr.from, repr.to
+            ))),
+        }
+    }
+}
+
+/// Holds when the current weather code, or any hourly code in the next
+/// `within_hours`, is one of `codes` (WMO codes, e.g. 95-99 for storms).
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WeatherCondition {
+    pub codes: Vec<u32>,
+    #[serde(default)]
+    pub within_hours: i64,
+}
+
+impl Condition {
+    fn holds(&self, now: NaiveDateTime, data: &AllData) -> bool {
+        if let Some(time) = &self.time {
+            let t = now.time();
+            let inside = if time.from <= time.to {
+                time.from <= t && t < time.to
+            } else {
+                time.from <= t || t < time.to
+            };
+            if !inside {
+                return false;
+            }
+        }
+
+        if let Some(weekdays) = &self.weekdays
+            && !weekdays.contains(&now.weekday())
+        {
+            return false;
+        }
+
+        if let Some(dates) = &self.dates {
+            let today = now.date();
+            let inside = match *dates {
+                DateRange::Dates(from, to) => from <= today && today <= to,
+                DateRange::Yearly(from, to) => {
+                    let day = (today.month(), today.day());
+                    if from <= to {
+                        from <= day && day <= to
+                    } else {
+                        from <= day || day <= to
+                    }
+                }
+            };
+            if !inside {
+                return false;
+            }
+        }
+
+        if let Some(feed) = &self.stale {
+            let stale = data.feed_error(feed).is_some()
+                || data
+                    .feed_status(feed)
+                    .is_some_and(|status| status.staleness >= Staleness::Stale);
+            if !stale {
+                return false;
+            }
+        }
+
+        if let Some(weather) = &self.weather
+            && !weather.holds(data)
+        {
+            return false;
+        }
+
+        if !self.any.is_empty() && !self.any.iter().any(|clause| clause.holds(now, data)) {
+            return false;
+        }
+
+        if let Some(clause) = &self.not
+            && clause.holds(now, data)
+        {
+            return false;
+        }
+
+        true
+    }
+}
+
+impl WeatherCondition {
+    /// Forecast times are wall-clock times in the forecast's own zone.
+    fn holds(&self, data: &AllData) -> bool {
+        let Ok(weather) = &data.weather else {
+            return false;
+        };
+        let weather = &weather.data;
+        let now = data.local_now(ZoneSource::Weather);
+
+        if self.codes.contains(&(weather.current.weather_code as u32)) {
+            return true;
+        }
+
+        let until = now + chrono::Duration::hours(self.within_hours);
+        weather
+            .hourly
+            .time
+            .iter()
+            .zip(&weather.hourly.weather_code)
+            .any(|(time, code)| {
+                // Hourly slots cover the hour they start, so the current one counts
+                NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M")
+                    .is_ok_and(|time| time + chrono::Duration::hours(1) > now && time <= until)
+                    && self.codes.contains(code)
+            })
+    }
+}
+
+/// Deterministically maps a 64-bit integer to a row id between 0 and count-1
+fn seed_to_index(seed: u64, count: i64) -> i64 {
+    // Just modulo to wrap around row count
+    (seed % (count as u64)) as i64
+}
+
+fn get_verse_by_seed<P: AsRef<FsPath>>(db_path: P, seed: u64) -> Result<Option<(String, String)>> {
+    let conn = Connection::open(db_path)?;
+
+    // Count total rows
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM verses", [], |row| row.get(0))?;
+
+    if count == 0 {
+        return Ok(None);
     }
 
     let index = seed_to_index(seed, count);
@@ -308,22 +1675,142 @@ fn get_verse_by_seed<P: AsRef<FsPath>>(db_path: P, seed: u64) -> Result<Option<(
     }
 }
 
+/// ---- Feed staleness ----
+
+/// How old a feed's `last_good` is, and whether it's past its `expires`.
+#[derive(Debug, Clone, Copy)]
+pub struct FeedAge {
+    pub age_hours: f64,
+    pub expired: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Staleness {
+    Fresh,
+    /// Still drawn, with a badge showing its age
+    Stale,
+    /// Replaced by a placeholder
+    TooOld,
+}
+
+/// An entry in the config's `"staleness"` map, keyed by feed name. An entry
+/// replaces that feed's defaults; a missing threshold means "never".
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+struct StalenessPolicy {
+    stale_after_hours: Option<f64>,
+    hide_after_hours: Option<f64>,
+}
+
+impl StalenessPolicy {
+    fn default_for(feed: &str) -> Self {
+        let (stale, hide) = match feed {
+            "weather" => (Some(1.0), Some(6.0)),
+            "battery" => (Some(24.0), Some(72.0)),
+            _ => (Some(24.0), None),
+        };
+        StalenessPolicy {
+            stale_after_hours: stale,
+            hide_after_hours: hide,
+        }
+    }
+
+    fn for_feed(policies: &HashMap<String, StalenessPolicy>, feed: &str) -> Self {
+        policies
+            .get(feed)
+            .copied()
+            .unwrap_or_else(|| Self::default_for(feed))
+    }
+
+    /// A passed `expires` makes data stale no matter how young it is.
+    fn classify(&self, age: &FeedAge) -> Staleness {
+        if self.hide_after_hours.is_some_and(|h| age.age_hours > h) {
+            Staleness::TooOld
+        } else if age.expired || self.stale_after_hours.is_some_and(|h| age.age_hours > h) {
+            Staleness::Stale
+        } else {
+            Staleness::Fresh
+        }
+    }
+}
+
+/// A loaded feed's age, classified against its policy.
+#[derive(Debug, Clone, Copy)]
+struct FeedStatus {
+    age_hours: f64,
+    staleness: Staleness,
+}
+
+impl FeedStatus {
+    fn new(feed: &str, age: FeedAge, policies: &HashMap<String, StalenessPolicy>) -> Self {
+        let staleness = StalenessPolicy::for_feed(policies, feed).classify(&age);
+        println!(
+            "{} data is {:.1} hours old ({:?})",
+            feed, age.age_hours, staleness
+        );
+
+        FeedStatus {
+            age_hours: age.age_hours,
+            staleness: staleness,
+        }
+    }
+}
+
+/// "45m", "5h", "3d"
+fn format_age(hours: f64) -> String {
+    if hours < 1.0 {
+        format!("{}m", (hours * 60.0).round().max(1.0))
+    } else if hours < 48.0 {
+        format!("{}h", hours.round())
+    } else {
+        format!("{}d", (hours / 24.0).round())
+    }
+}
+
 /// ---- Trait-based size access to remove boilerplate ----
 
 trait HasSize {
     fn size(&self) -> &Size;
+    fn box_layout(&self) -> &BoxLayout;
 }
 
-impl HasSize for SizedNode {
+impl<O> HasSize for SizedNode<O> {
     fn size(&self) -> &Size {
         &self.size
     }
+
+    fn box_layout(&self) -> &BoxLayout {
+        &self.layout
+    }
 }
 
 impl HasSize for ContainerNode {
     fn size(&self) -> &Size {
         &self.size
     }
+
+    fn box_layout(&self) -> &BoxLayout {
+        &self.layout
+    }
+}
+
+impl HasSize for GridNode {
+    fn size(&self) -> &Size {
+        &self.size
+    }
+
+    fn box_layout(&self) -> &BoxLayout {
+        &self.layout
+    }
+}
+
+impl HasSize for OverlayNode {
+    fn size(&self) -> &Size {
+        &self.size
+    }
+
+    fn box_layout(&self) -> &BoxLayout {
+        &self.layout
+    }
 }
 
 impl LayoutNode {
@@ -331,6 +1818,8 @@ impl LayoutNode {
     fn size(&self) -> &Size {
         match self {
             LayoutNode::Container(n) => n.size(),
+            LayoutNode::Grid(n) => n.size(),
+            LayoutNode::Overlay(n) => n.size(),
             LayoutNode::Date(n) => n.size(),
             LayoutNode::Todo(n) => n.size(),
             LayoutNode::HLine(n) => n.size(),
@@ -342,33 +1831,107 @@ impl LayoutNode {
             LayoutNode::Verse(n) => n.size(),
         }
     }
-}
 
-/// Small helpers to extract numeric values from Size.
-fn fixed_from(size: &Size) -> u64 {
-    match size {
-        Size::Px(v) => *v,
-        Size::Unit(_) => 0,
+    /// Margin, padding and min/max for any node.
+    fn box_layout(&self) -> &BoxLayout {
+        match self {
+            LayoutNode::Container(n) => n.box_layout(),
+            LayoutNode::Grid(n) => n.box_layout(),
+            LayoutNode::Overlay(n) => n.box_layout(),
+            LayoutNode::Date(n) => n.box_layout(),
+            LayoutNode::Todo(n) => n.box_layout(),
+            LayoutNode::HLine(n) => n.box_layout(),
+            LayoutNode::VLine(n) => n.box_layout(),
+            LayoutNode::Weather(n) => n.box_layout(),
+            LayoutNode::Allowance(n) => n.box_layout(),
+            LayoutNode::Countdown(n) => n.box_layout(),
+            LayoutNode::Battery(n) => n.box_layout(),
+            LayoutNode::Verse(n) => n.box_layout(),
+        }
+    }
+
+    /// Envelope feeds the widget draws from, for staleness checks.
+    fn feeds(&self) -> &'static [&'static str] {
+        match self {
+            LayoutNode::Weather(_) => &["weather"],
+            LayoutNode::Allowance(_) => &["cleaning", "names", "balances", "upcoming_payouts"],
+            LayoutNode::Todo(_) => &["todo"],
+            LayoutNode::Battery(_) => &["battery"],
+            LayoutNode::Countdown(_) => &["dates"],
+            LayoutNode::Container(_)
+            | LayoutNode::Grid(_)
+            | LayoutNode::Overlay(_)
+            | LayoutNode::Date(_)
+            | LayoutNode::HLine(_)
+            | LayoutNode::VLine(_)
+            | LayoutNode::Verse(_) => &[],
+        }
+    }
+
+    /// Whether the node lays out children rather than drawing a widget.
+    fn is_container(&self) -> bool {
+        matches!(
+            self,
+            LayoutNode::Container(_) | LayoutNode::Grid(_) | LayoutNode::Overlay(_)
+        )
+    }
+
+    /// Border box (after margin) and content box (after padding) of the
+    /// slot the splitter gave this node.
+    fn boxes(&self, x: i32, y: i32, width: i32, height: i32) -> [(i32, i32, i32, i32); 2] {
+        let layout = self.box_layout();
+        let border = layout.margin.inset(x, y, width, height);
+        let content = layout.padding.inset(border.0, border.1, border.2, border.3);
+        [border, content]
+    }
+
+    /// Per-node dithering override, if any.
+    fn dither(&self) -> Option<DitherMethod> {
+        match self {
+            LayoutNode::Container(n) => n.dither,
+            LayoutNode::Grid(n) => n.dither,
+            LayoutNode::Overlay(n) => n.dither,
+            LayoutNode::Date(n) => n.dither,
+            LayoutNode::Todo(n) => n.dither,
+            LayoutNode::HLine(n) | LayoutNode::VLine(n) => n.dither,
+            LayoutNode::Weather(n) => n.dither,
+            LayoutNode::Allowance(n) => n.dither,
+            LayoutNode::Countdown(n) => n.dither,
+            LayoutNode::Battery(n) => n.dither,
+            LayoutNode::Verse(n) => n.dither,
+        }
     }
 }
 
-fn scaled_from(size: &Size) -> f64 {
+/// Pixel size of a px or percent child, before min/max; None for "u" children.
+fn fixed_from(size: &Size, split_dim_pix: i32) -> Option<f64> {
     match size {
-        Size::Px(_) => 0.0,
-        Size::Unit(v) => *v,
+        Size::Px(v) => Some(*v as f64),
+        Size::Percent(p) => Some(split_dim_pix.max(0) as f64 * p / 100.0),
+        Size::Unit(_) => None,
     }
 }
 
 /// ---- Rendering helpers ----
 
-fn days_between(date: NaiveDate) -> i64 {
-    let today = Local::now().date_naive();
+fn days_between(date: NaiveDate, today: NaiveDate) -> i64 {
     (date - today).num_days()
 }
 
+/// The day of each significant date, or which one isn't a YYYY-MM-DD date.
+fn countdown_targets(dates: &[SignificantDate]) -> Result<Vec<NaiveDate>, String> {
+    dates
+        .iter()
+        .map(|date| {
+            NaiveDate::parse_from_str(&date.date, "%Y-%m-%d")
+                .map_err(|e| format!("'{}' has a bad date '{}': {}", date.name, date.date, e))
+        })
+        .collect()
+}
+
 const WEEKDAYS3: [&str; 7] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat"];
 
-fn draw_colored_line(canvas: &Canvas, start: Point, end: Point, color: Color) {
+fn draw_colored_line(canvas: &RenderCanvas, start: Point, end: Point, color: Color) {
     let mut paint = Paint::default();
     paint.set_color(color); // medium gray
     paint.set_anti_alias(true); // Smooth edges
@@ -377,12 +1940,7 @@ fn draw_colored_line(canvas: &Canvas, start: Point, end: Point, color: Color) {
     canvas.draw_line(start, end, &paint);
 }
 
-fn draw_line(canvas: &Canvas, start: Point, end: Point) {
-    draw_colored_line(canvas, start, end, Color::from_rgb(200, 200, 200))
-}
-
-#[allow(dead_code)]
-fn draw_rect_thing(canvas: &Canvas, x: i32, y: i32, width: i32, height: i32) {
+fn draw_rect_thing(canvas: &RenderCanvas, x: i32, y: i32, width: i32, height: i32) {
     let margin = 0; //6;
     let mut paint = Paint::default();
     paint.set_color(Color::from_rgb(0, 128, 255));
@@ -409,7 +1967,7 @@ fn line_height(font: &Font) -> f32 {
 }
 
 fn draw_text_blob_with_color(
-    canvas: &Canvas,
+    canvas: &RenderCanvas,
     font: &Font,
     x: i32,
     y: i32,
@@ -421,12 +1979,15 @@ fn draw_text_blob_with_color(
     paint.set_color(color);
     paint.set_anti_alias(true);
 
-    let xoff = if align > 0.0 {
-        let ww = font.measure_str(text, None).0;
-        -align * ww
-    } else {
-        0.0
-    };
+    let (advance, bounds) = font.measure_str(text, None);
+    let xoff = if align > 0.0 { -align * advance } else { 0.0 };
+
+    canvas.mark_text(Rect::from_xywh(
+        x as f32 + xoff + bounds.left,
+        y as f32 + bounds.top,
+        bounds.width(),
+        bounds.height(),
+    ));
 
     if let Some(blob) = TextBlob::from_str(text, &font) {
         canvas.draw_text_blob(&blob, (x as f32 + xoff, y as f32), &paint);
@@ -436,134 +1997,264 @@ fn draw_text_blob_with_color(
     }
 }
 
-fn draw_text_blob(canvas: &Canvas, font: &Font, x: i32, y: i32, text: &str) {
+fn draw_text_blob(canvas: &RenderCanvas, font: &Font, x: i32, y: i32, text: &str) {
     draw_text_blob_with_color(canvas, font, x, y, text, Color::BLACK, 0.0);
 }
 
-fn load_font_from_file(font_mgr: &FontMgr, path: &str, size: f32) -> Font {
+fn load_typeface_from_file(font_mgr: &FontMgr, path: &str) -> Typeface {
     // Read the font file into memory
     let font_bytes = fs::read(path).expect("Failed to read font file");
 
     // Create the typeface via the FontMgr
-    let typeface = font_mgr
+    font_mgr
         .new_from_data(&font_bytes, 0)
-        .unwrap_or_else(|| Font::default().typeface()); // fallback to default Font's typeface
+        .unwrap_or_else(|| Font::default().typeface()) // fallback to default Font's typeface
+}
 
-    // Create the Font
-    Font::from_typeface(&typeface, size)
+fn load_font_from_file(font_mgr: &FontMgr, path: &str, size: f32) -> Font {
+    Font::from_typeface(&load_typeface_from_file(font_mgr, path), size)
+}
+
+/// Where FontBoss reads each typeface from.
+struct FontFiles {
+    regular: &'static str,
+    italic: &'static str,
+    bold: &'static str,
+    roboto_extra_bold: &'static str,
+    emoji: &'static str,
 }
 
+/// The fonts next to the binary on the render host.
+const FONT_FILES: FontFiles = FontFiles {
+    regular: "Crimson_Pro/static/CrimsonPro-Regular.ttf",
+    italic: "Crimson_Pro/static/CrimsonPro-Italic.ttf",
+    bold: "Crimson_Pro/static/CrimsonPro-Bold.ttf",
+    roboto_extra_bold: "Roboto/Roboto-ExtraBold.ttf",
+    emoji: "NotoEmoji.ttf",
+};
+
+/// Fonts and icons, loaded once and kept for every render.
 struct FontBoss {
-    font_mgr: skia_safe::FontMgr,
     pub main_font: Font,
     pub emoji_font: Font,
+    regular: Typeface,
+    italic: Typeface,
+    bold: Typeface,
+    roboto_extra_bold: Typeface,
+    // Rendered weather icons by (file, size)
+    icons: RefCell<HashMap<(&'static str, u32), Image>>,
 }
 
 impl FontBoss {
     pub fn load_roboto_extra_bold_font(&self, size: f32) -> Font {
-        load_font_from_file(&self.font_mgr, "Roboto/Roboto-ExtraBold.ttf", size)
+        Font::from_typeface(&self.roboto_extra_bold, size)
     }
 
     pub fn load_font(&self, size: f32) -> Font {
-        load_font_from_file(
-            &self.font_mgr,
-            "Crimson_Pro/static/CrimsonPro-Regular.ttf",
-            size,
-        )
+        Font::from_typeface(&self.regular, size)
     }
 
     pub fn load_italic_font(&self, size: f32) -> Font {
-        load_font_from_file(
-            &self.font_mgr,
-            "Crimson_Pro/static/CrimsonPro-Italic.ttf",
-            size,
-        )
+        Font::from_typeface(&self.italic, size)
     }
 
     pub fn load_bold_font(&self, size: f32) -> Font {
-        load_font_from_file(
-            &self.font_mgr,
-            "Crimson_Pro/static/CrimsonPro-Bold.ttf",
-            size,
-        )
+        Font::from_typeface(&self.bold, size)
+    }
+
+    /// A font picked in layout options.
+    pub fn load_option(&self, font: &FontOption) -> Font {
+        let typeface = match font.face {
+            FontFace::Regular => &self.regular,
+            FontFace::Italic => &self.italic,
+            FontFace::Bold => &self.bold,
+            FontFace::RobotoExtraBold => &self.roboto_extra_bold,
+        };
+        Font::from_typeface(typeface, font.size)
+    }
+
+    /// The icon for a WMO weather code, rasterized at `dim` x `dim` on first use.
+    pub fn weather_icon(&self, code: u8, dim: u32) -> Result<Image, Box<dyn std::error::Error>> {
+        let key = (wmo_code_to_icon(code), dim);
+        if let Some(image) = self.icons.borrow().get(&key) {
+            return Ok(image.clone());
+        }
+
+        let image = code_to_svg(code, dim)?.image;
+        self.icons.borrow_mut().insert(key, image.clone());
+        Ok(image)
     }
 
     pub fn new() -> Self {
+        FontBoss::from_files(&FONT_FILES)
+    }
+
+    fn from_files(files: &FontFiles) -> Self {
         let font_mgr = skia_safe::FontMgr::default();
-        let font =
-            load_font_from_file(&font_mgr, "Crimson_Pro/static/CrimsonPro-Regular.ttf", 25.0);
-        let emoji_font = load_font_from_file(&font_mgr, "NotoEmoji.ttf", 30.0);
+        let regular = load_typeface_from_file(&font_mgr, files.regular);
+        let emoji_font = load_font_from_file(&font_mgr, files.emoji, 30.0);
 
         FontBoss {
-            font_mgr: font_mgr,
-            main_font: font,
+            main_font: Font::from_typeface(&regular, 25.0),
             emoji_font: emoji_font,
+            italic: load_typeface_from_file(&font_mgr, files.italic),
+            bold: load_typeface_from_file(&font_mgr, files.bold),
+            roboto_extra_bold: load_typeface_from_file(&font_mgr, files.roboto_extra_bold),
+            regular: regular,
+            icons: RefCell::new(HashMap::new()),
         }
     }
 }
 
-/// ---- Layout engine: container splitting and child dispatch ----
+/// ---- Render canvas: a Skia canvas plus quantization hints ----
 
-fn handle_container(
-    canvas: &Canvas,
-    font_boss: &FontBoss,
-    container: &ContainerNode,
-    split: &SplitDirection,
+#[derive(Debug, Clone, Copy)]
+enum RegionKind {
+    /// A layout node picked its own method
+    Node(DitherMethod),
+    /// Glyphs, which get the text method so their edges stay sharp
+    Text,
+    /// Icons, gradients and charts, which keep the node's method
+    Image,
+}
+
+/// A rectangle of the canvas and how it should be quantized.
+#[derive(Debug, Clone, Copy)]
+struct QuantRegion {
     x: i32,
     y: i32,
     width: i32,
     height: i32,
-    data: &AllData,
-) {
-    // Determine the dimension (in pixels) along which we split children.
-    let split_dim_pix = match split {
-        SplitDirection::Horizontal => width,
-        SplitDirection::Vertical => height,
-    };
+    kind: RegionKind,
+}
+
+/// Wraps the Skia canvas so widgets can note how their pixels should be
+/// quantized while they draw. Derefs to `Canvas` for all the drawing.
+struct RenderCanvas<'a> {
+    canvas: &'a Canvas,
+    regions: RefCell<Vec<QuantRegion>>,
+    /// Widget border boxes, for outlining in the preview
+    outlines: RefCell<Vec<(i32, i32, i32, i32)>>,
+}
 
-    // 1) Sum fixed pixels among children
-    let mut fixed_sum: u64 = 0;
-    for child in &container.entries {
-        fixed_sum += fixed_from(child.size());
+impl<'a> RenderCanvas<'a> {
+    fn new(canvas: &'a Canvas) -> Self {
+        RenderCanvas {
+            canvas,
+            regions: RefCell::new(Vec::new()),
+            outlines: RefCell::new(Vec::new()),
+        }
     }
 
-    // 2) Compute leftover to be distributed to "Unit" children.
-    let leftover = split_dim_pix.saturating_sub(fixed_sum as i32);
+    fn mark(&self, x: i32, y: i32, width: i32, height: i32, kind: RegionKind) {
+        self.regions.borrow_mut().push(QuantRegion {
+            x,
+            y,
+            width,
+            height,
+            kind,
+        });
+    }
 
-    // 3) Sum the scaled units among children
-    let mut scale_sum = 0.0f64;
-    for child in &container.entries {
-        scale_sum += scaled_from(child.size());
+    fn mark_dither(&self, x: i32, y: i32, width: i32, height: i32, method: DitherMethod) {
+        self.mark(x, y, width, height, RegionKind::Node(method));
     }
 
-    // 4) compute each child's size and start offset
-    let mut sizes: Vec<i32> = Vec::with_capacity(container.entries.len());
-    let mut starts: Vec<i32> = Vec::with_capacity(container.entries.len());
-    let mut cursor = 0i32;
+    /// Marks drawn bounds, grown a pixel each way to cover anti-aliasing.
+    fn mark_rect(&self, rect: Rect, kind: RegionKind) {
+        let x0 = rect.left.floor() as i32 - 1;
+        let y0 = rect.top.floor() as i32 - 1;
+        let x1 = rect.right.ceil() as i32 + 1;
+        let y1 = rect.bottom.ceil() as i32 + 1;
+        self.mark(x0, y0, x1 - x0, y1 - y0, kind);
+    }
 
-    for child in &container.entries {
-        starts.push(cursor);
+    fn mark_text(&self, rect: Rect) {
+        self.mark_rect(rect, RegionKind::Text);
+    }
 
-        let fs = fixed_from(child.size());
-        let ss = scaled_from(child.size());
+    fn mark_image(&self, rect: Rect) {
+        self.mark_rect(rect, RegionKind::Image);
+    }
 
-        let child_size = if fs > 0 {
-            fs as i32
-        } else {
-            // if scale_sum is zero (no scalable children) but leftover > 0, give zero
-            if scale_sum <= 0.0 {
-                0
-            } else {
-                ((leftover as f64) * (ss / scale_sum)) as i32
-            }
+    /// One method per pixel. Node regions are applied first, nested nodes
+    /// winning over their containers. Text and image regions then follow in
+    /// drawing order, so whatever was drawn last over a pixel decides.
+    fn method_map(
+        &self,
+        width: usize,
+        height: usize,
+        default: DitherMethod,
+        text: DitherMethod,
+    ) -> Vec<DitherMethod> {
+        let regions = self.regions.borrow();
+        let clip = |region: &QuantRegion| {
+            let x0 = region.x.clamp(0, width as i32) as usize;
+            let y0 = region.y.clamp(0, height as i32) as usize;
+            let x1 = (region.x + region.width).clamp(0, width as i32) as usize;
+            let y1 = (region.y + region.height).clamp(0, height as i32) as usize;
+            (x0, y0, x1, y1)
         };
 
-        sizes.push(child_size);
-        cursor += child_size;
-    }
-
-    // 5) Dispatch each child
-    for (i, child) in container.entries.iter().enumerate() {
+        let mut node_map = vec![default; width * height];
+        for region in regions.iter() {
+            if let RegionKind::Node(method) = region.kind {
+                let (x0, y0, x1, y1) = clip(region);
+                for y in y0..y1 {
+                    node_map[y * width + x0..y * width + x1].fill(method);
+                }
+            }
+        }
+
+        let mut map = node_map.clone();
+        for region in regions.iter() {
+            let (x0, y0, x1, y1) = clip(region);
+            for y in y0..y1 {
+                let row = y * width + x0..y * width + x1;
+                match region.kind {
+                    RegionKind::Node(_) => {}
+                    RegionKind::Text => map[row].fill(text),
+                    RegionKind::Image => map[row.clone()].copy_from_slice(&node_map[row]),
+                }
+            }
+        }
+
+        map
+    }
+}
+
+impl Deref for RenderCanvas<'_> {
+    type Target = Canvas;
+
+    fn deref(&self) -> &Canvas {
+        self.canvas
+    }
+}
+
+/// ---- Layout engine: container splitting and child dispatch ----
+
+fn handle_container(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    container: &ContainerNode,
+    split: &SplitDirection,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    data: &AllData,
+) {
+    // Determine the dimension (in pixels) along which we split children.
+    let split_dim_pix = match split {
+        SplitDirection::Horizontal => width,
+        SplitDirection::Vertical => height,
+    };
+
+    let entries = shown_entries(&container.entries, data);
+    let (starts, sizes) = split_sizes(&entries, container.align, split_dim_pix);
+
+    // Dispatch each child
+    for (i, child) in entries.iter().enumerate() {
         let sx = starts[i];
         let s = sizes[i];
 
@@ -578,1730 +2269,7002 @@ fn handle_container(
     }
 }
 
-// Draws a smooth Catmull-Rom spline through the points
-// and fills the area under it down to the baseline.
-fn fill_catmull_rom_area(canvas: &Canvas, points: &[Point], baseline_y: f32) {
-    if points.len() < 2 {
-        return;
-    }
-
-    // --- Create the fill path ---
-    let mut fill_path = Path::new();
-    fill_path.move_to(Point::new(points[0].x, baseline_y)); // baseline start
-    fill_path.line_to(points[0]); // move up to first point
-
-    // Build Catmull-Rom spline
-    for i in 0..points.len() - 1 {
-        let p0 = if i == 0 { points[0] } else { points[i - 1] };
-        let p1 = points[i];
-        let p2 = points[i + 1];
-        let p3 = if i + 2 < points.len() {
-            points[i + 2]
-        } else {
-            points[points.len() - 1]
-        };
+/// The children whose `when` holds at the time being rendered, in order.
+fn shown_entries<'a>(entries: &'a [LayoutNode], data: &AllData) -> Vec<&'a LayoutNode> {
+    let now = data.now.naive_local();
+    entries
+        .iter()
+        .filter(|child| {
+            child
+                .box_layout()
+                .when
+                .as_ref()
+                .is_none_or(|when| when.holds(now, data))
+        })
+        .collect()
+}
 
-        let c1 = Point::new(p1.x + (p2.x - p0.x) / 6.0, p1.y + (p2.y - p0.y) / 6.0);
-        let c2 = Point::new(p2.x - (p3.x - p1.x) / 6.0, p2.y - (p3.y - p1.y) / 6.0);
+/// Start offsets and sizes of a container's children along its split axis.
+/// The run is shifted by `align` when it doesn't fill the container.
+fn split_sizes(entries: &[&LayoutNode], align: Align, split_dim_pix: i32) -> (Vec<i32>, Vec<i32>) {
+    let items: Vec<(&Size, &BoxLayout)> = entries
+        .iter()
+        .map(|child| (child.size(), child.box_layout()))
+        .collect();
+    let sizes = share_space(&items, split_dim_pix);
+
+    let total: i32 = sizes.iter().sum();
+    let spare = (split_dim_pix - total).max(0);
+    let mut cursor = match align {
+        Align::Start => 0,
+        Align::Center => spare / 2,
+        Align::End => spare,
+    };
 
-        fill_path.cubic_to(c1, c2, p2);
+    let mut starts: Vec<i32> = Vec::with_capacity(sizes.len());
+    for size in &sizes {
+        starts.push(cursor);
+        cursor += size;
     }
 
-    // Line down to baseline at last x
-    fill_path.line_to(Point::new(points[points.len() - 1].x, baseline_y));
-    // Close path (connects back to baseline start)
-    fill_path.close();
+    (starts, sizes)
+}
 
-    // --- Fill area ---
-    let mut paint_fill = Paint::default();
-    paint_fill.set_color(Color::from_rgb(220, 220, 220));
-    paint_fill.set_anti_alias(true);
-    paint_fill.set_style(PaintStyle::Fill);
-    canvas.draw_path(&fill_path, &paint_fill);
+/// Pixel sizes for a run of items sharing `dim_pix`. Px and percent items
+/// get their pixels; "u" items share what's left. An item pinned by
+/// min/max drops out of the sharing and the others split the rest.
+fn share_space(items: &[(&Size, &BoxLayout)], dim_pix: i32) -> Vec<i32> {
+    // 1) Fixed items are settled straight away
+    let mut settled: Vec<Option<f64>> = items
+        .iter()
+        .map(|(size, layout)| fixed_from(size, dim_pix).map(|px| layout.clamp(px)))
+        .collect();
 
-    // --- Stroke only the curve ---
-    let mut curve_path = Path::new();
-    curve_path.move_to(points[0]);
+    // 2) Share the leftover among "u" items until none hits a min/max
+    loop {
+        let fixed_sum: f64 = settled.iter().flatten().sum();
+        let leftover = dim_pix.saturating_sub(fixed_sum as i32);
 
-    for i in 0..points.len() - 1 {
-        let p0 = if i == 0 { points[0] } else { points[i - 1] };
-        let p1 = points[i];
-        let p2 = points[i + 1];
-        let p3 = if i + 2 < points.len() {
-            points[i + 2]
-        } else {
-            points[points.len() - 1]
-        };
+        let mut scale_sum = 0.0f64;
+        for ((size, _), px) in items.iter().zip(&settled) {
+            if let (Size::Unit(u), None) = (size, px) {
+                scale_sum += u;
+            }
+        }
 
-        let c1 = Point::new(p1.x + (p2.x - p0.x) / 6.0, p1.y + (p2.y - p0.y) / 6.0);
-        let c2 = Point::new(p2.x - (p3.x - p1.x) / 6.0, p2.y - (p3.y - p1.y) / 6.0);
+        let mut pinned = false;
+        for ((size, layout), px) in items.iter().zip(settled.iter_mut()) {
+            let (Size::Unit(u), None) = (size, &px) else {
+                continue;
+            };
+            // if scale_sum is zero (no scalable items) but leftover > 0, give zero
+            let share = if scale_sum <= 0.0 {
+                0.0
+            } else {
+                (leftover as f64) * (u / scale_sum)
+            };
+            let clamped = layout.clamp(share);
+            if clamped != share {
+                *px = Some(clamped);
+                pinned = true;
+            }
+        }
+        if pinned {
+            continue;
+        }
 
-        curve_path.cubic_to(c1, c2, p2);
+        for ((size, _), px) in items.iter().zip(settled.iter_mut()) {
+            if let (Size::Unit(u), None) = (size, &px) {
+                *px = Some(if scale_sum <= 0.0 {
+                    0.0
+                } else {
+                    (leftover as f64) * (u / scale_sum)
+                });
+            }
+        }
+        break;
     }
 
-    let mut paint_curve = Paint::default();
-    paint_curve.set_color(Color::BLACK);
-    paint_curve.set_style(PaintStyle::Stroke);
-    paint_curve.set_stroke_width(2.0);
-    paint_curve.set_anti_alias(true);
-
-    canvas.draw_path(&curve_path, &paint_curve);
+    settled
+        .into_iter()
+        .map(|px| px.unwrap_or(0.0) as i32)
+        .collect()
 }
 
-fn draw_filled_circle(canvas: &Canvas, center: Point, radius: f32, color: Color) {
-    let mut paint = Paint::default();
-    paint.set_color(color); // red color
-    paint.set_anti_alias(true); // smooth edges
-    paint.set_style(PaintStyle::Fill); // fill, not stroke
-
-    canvas.draw_circle(center, radius, &paint);
+fn handle_grid(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    grid: &GridNode,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    data: &AllData,
+) {
+    let entries = shown_entries(&grid.entries, data);
+    for (child, rect) in entries
+        .iter()
+        .zip(grid_rects(grid, &entries, width, height))
+    {
+        let Some((cx, cy, cw, ch)) = rect else {
+            println!("Skipping a grid entry with no cell to go in.");
+            continue;
+        };
+        handle_child(canvas, font_boss, child, x + cx, y + cy, cw, ch, data);
+    }
 }
 
-fn draw_hourly(
-    canvas: &Canvas,
-    mini_font: &Font,
+fn handle_overlay(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    overlay: &OverlayNode,
     x: i32,
     y: i32,
     width: i32,
     height: i32,
-    values: &[f32],
-    symbol: &str,
-    range: (f32, f32),
+    data: &AllData,
 ) {
-    // draw_rect_thing(canvas, x, y, width, height);
-
-    let dp_width = (width as f32) / (values.len() - 1) as f32;
-
-    let (min, max) = range;
-
-    let vsize = max - min;
+    for child in shown_entries(&overlay.entries, data) {
+        let (cx, cy, cw, ch) = overlay_rect(child, width, height);
+        handle_child(canvas, font_boss, child, x + cx, y + cy, cw, ch, data);
+    }
+}
 
-    println!("min {} max {} vsize {}", min, max, vsize);
+/// Start offsets and sizes of a grid's tracks, `gap` pixels apart.
+fn track_sizes(tracks: &[Size], gap: i32, dim_pix: i32) -> (Vec<i32>, Vec<i32>) {
+    let unclamped = BoxLayout::default();
+    let items: Vec<(&Size, &BoxLayout)> = tracks.iter().map(|track| (track, &unclamped)).collect();
+    let gaps = gap * (tracks.len() as i32 - 1).max(0);
+    let sizes = share_space(&items, dim_pix - gaps);
 
-    let graph_base = 10.0;
-    let graph_offset = y as f32 + 5.0;
-    let graph_height = (height - 10) as f32 - graph_base;
-    let mut temp_points: Vec<Point> = Vec::with_capacity(values.len());
-    for i in 0..values.len() {
-        let val = (values[i] - min) / vsize;
+    let mut starts: Vec<i32> = Vec::with_capacity(sizes.len());
+    let mut cursor = 0;
+    for size in &sizes {
+        starts.push(cursor);
+        cursor += size + gap;
+    }
 
-        let px = x as f32 + i as f32 * dp_width;
-        let py = graph_offset + (1.0 - val) * graph_height as f32;
-        temp_points.push(Point::new(px, py));
+    (starts, sizes)
+}
 
-        // draw_filled_circle(canvas, Point::new(px, py), 5.0);
+/// The cell each of a grid's `entries` lands in. Children with a `cell`
+/// are placed first, then the rest flow into free cells in reading order.
+/// None means the cell is off the grid or no free cell was left.
+fn grid_placements(grid: &GridNode, entries: &[&LayoutNode]) -> Vec<Option<GridCell>> {
+    let (rows, columns) = (grid.rows.len(), grid.columns.len());
+    let mut taken = vec![false; rows * columns];
+    let mut placements: Vec<Option<GridCell>> = vec![None; entries.len()];
+
+    for (child, placement) in entries.iter().zip(placements.iter_mut()) {
+        let Some(cell) = child.box_layout().cell else {
+            continue;
+        };
+        if cell.row_span == 0
+            || cell.column_span == 0
+            || cell.row + cell.row_span > rows
+            || cell.column + cell.column_span > columns
+        {
+            continue;
+        }
+        for row in cell.row..cell.row + cell.row_span {
+            for column in cell.column..cell.column + cell.column_span {
+                taken[row * columns + column] = true;
+            }
+        }
+        *placement = Some(cell);
     }
-    //draw_catmull_rom_curve(canvas, &temp_points);
-    fill_catmull_rom_area(
-        canvas,
-        &temp_points,
-        graph_offset + graph_height + graph_base,
-    );
 
-    for i in 0..values.len() {
-        let pct = i as f32 / (values.len() - 1) as f32;
-        // let val = (values[i] - min) / vsize;
-        let px = x as f32 + i as f32 * dp_width;
-        // let py = graph_offset + val * graph_height as f32;
-        if i % 2 == 0 {
-            draw_text_blob_with_color(
-                canvas,
-                &mini_font,
-                px as i32,
-                (graph_offset + graph_height) as i32 + 28,
-                &format!("{}{}", values[i].round(), symbol),
-                Color::from_rgb(128, 128, 128),
-                pct,
-            );
+    let mut next = 0;
+    for (child, placement) in entries.iter().zip(placements.iter_mut()) {
+        if child.box_layout().cell.is_some() {
+            continue;
+        }
+        while next < taken.len() && taken[next] {
+            next += 1;
         }
+        if next == taken.len() {
+            break;
+        }
+        taken[next] = true;
+        *placement = Some(GridCell {
+            row: next / columns,
+            column: next % columns,
+            row_span: 1,
+            column_span: 1,
+        });
     }
+
+    placements
 }
 
-fn draw_box_with_gradient(
-    canvas: &Canvas,
-    x: i32,
-    y: i32,
+/// Rectangles of a grid's `entries`, relative to the grid's content box.
+fn grid_rects(
+    grid: &GridNode,
+    entries: &[&LayoutNode],
     width: i32,
     height: i32,
-    top_color: Color,
-    bottom_color: Color,
-) {
-    let margin = 0;
-
-    let rect = Rect::from_xywh(
-        (x + margin) as f32,
-        (y + margin) as f32,
-        (width - margin * 2) as f32,
-        (height - margin * 2) as f32,
-    );
-    let rrect = RRect::new_rect_xy(rect, 8.0, 8.0);
-
-    // --- Gradient fill ---
-    let start_color = Color4f::from(top_color);
-    let end_color = Color4f::from(bottom_color);
-
-    // make a slice explicitly
-    let colors_slice: &[Color4f] = &[start_color, end_color];
+) -> Vec<Option<(i32, i32, i32, i32)>> {
+    let (column_starts, column_sizes) = track_sizes(&grid.columns, grid.gap, width);
+    let (row_starts, row_sizes) = track_sizes(&grid.rows, grid.gap, height);
+
+    // Offset and extent of `count` tracks starting at `first`, gaps included
+    let span = |starts: &[i32], sizes: &[i32], first: usize, count: usize| {
+        let last = first + count - 1;
+        (starts[first], starts[last] + sizes[last] - starts[first])
+    };
 
-    let shader = gradient_shader::linear(
-        (
-            Point::new(rect.left, rect.top),
-            Point::new(rect.left, rect.bottom),
-        ), // tuple of points
-        colors_slice, // explicit slice
-        None,
-        TileMode::Clamp,
-        None,
-        None,
-    );
+    grid_placements(grid, entries)
+        .into_iter()
+        .map(|placement| {
+            placement.map(|cell| {
+                let (x, width) = span(&column_starts, &column_sizes, cell.column, cell.column_span);
+                let (y, height) = span(&row_starts, &row_sizes, cell.row, cell.row_span);
+                (x, y, width, height)
+            })
+        })
+        .collect()
+}
 
-    let mut fill_paint = Paint::default();
-    fill_paint.set_anti_alias(true);
-    fill_paint.set_style(PaintStyle::Fill);
-    fill_paint.set_shader(shader);
+/// Rectangle of an overlay child, relative to the overlay's content box.
+/// Px and percent `width`/`height` shrink the child, capped at the overlay's
+/// size; anything else fills it.
+fn overlay_rect(child: &LayoutNode, width: i32, height: i32) -> (i32, i32, i32, i32) {
+    let layout = child.box_layout();
+    let extent = |size: &Option<Size>, dim_pix: i32| {
+        size.as_ref()
+            .and_then(|size| fixed_from(size, dim_pix))
+            .map_or(dim_pix, |px| (px as i32).min(dim_pix))
+    };
+    let child_width = extent(&layout.width, width);
+    let child_height = extent(&layout.height, height);
+
+    let (fx, fy) = layout.anchor.offsets();
+    (
+        ((width - child_width) as f64 * fx) as i32,
+        ((height - child_height) as f64 * fy) as i32,
+        child_width,
+        child_height,
+    )
+}
 
-    canvas.draw_rrect(rrect, &fill_paint);
+/// ---- Layout validation: schema and geometry checks with source locations ----
+
+/// Every `"type"` LayoutNode accepts; keep in step with its serde renames.
+const LAYOUT_NODE_TYPES: [&str; 12] = [
+    "container",
+    "grid",
+    "overlay",
+    "date",
+    "todo",
+    "hline",
+    "vline",
+    "weather",
+    "allowance",
+    "countdown",
+    "battery",
+    "verse",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Severity {
+    Warning,
+    Error,
+}
 
-    // --- Black outline ---
-    let mut stroke_paint = Paint::default();
-    stroke_paint.set_color(Color::BLACK);
-    stroke_paint.set_anti_alias(true);
-    stroke_paint.set_style(PaintStyle::Stroke);
-    stroke_paint.set_stroke_width(2.0);
+#[derive(Debug)]
+struct LayoutIssue {
+    severity: Severity,
+    /// JSON path like `$.entries[2].size`
+    path: String,
+    /// 1-based line and column of the value at `path`
+    position: Option<(usize, usize)>,
+    message: String,
+}
 
-    canvas.draw_rrect(rrect, &stroke_paint);
+impl std::fmt::Display for LayoutIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.position {
+            Some((line, column)) => write!(
+                f,
+                "{} at {} (line {}, column {}): {}",
+                severity, self.path, line, column, self.message
+            ),
+            None => write!(f, "{} at {}: {}", severity, self.path, self.message),
+        }
+    }
 }
 
-fn draw_temp_gradient(canvas: &Canvas, x: i32, y: i32, width: i32, height: i32) {
-    draw_box_with_gradient(
-        canvas,
-        x,
-        y,
-        width,
-        height,
-        Color::from_rgb(180, 180, 180),
-        Color::from_rgb(240, 240, 240),
-    );
+/// Walks JSON text recording where each value starts, keyed by JSON path.
+/// Stops quietly at malformed input; serde reports that with its own position.
+struct JsonScanner<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+    positions: HashMap<String, (usize, usize)>,
 }
 
-fn wmo_code_to_icon(code: u8) -> &'static str {
-    match code {
-        0 => "sunny-29.svg",                  // Clear sky
-        1 | 2 | 3 => "partly-cloudy-5.svg",   // Mainly clear, partly cloudy, overcast
-        45 | 48 => "fog-85.svg",              // Fog / depositing rime fog
-        51 | 53 | 55 => "light-rain-90.svg",  // Drizzle
-        56 | 57 => "sleet_03.svg",            // Freezing drizzle
-        61 | 63 | 65 => "shower-rain-1.svg",  // Rain showers
-        66 | 67 => "sleet_04.svg",            // Freezing rain
-        71 | 73 | 75 => "slight-snow_01.svg", // Snow fall
-        77 => "slight-snow.svg",              // Snow grains
-        80 | 81 | 82 => "shower-rain-1.svg",  // Rain showers
-        85 | 86 => "medium-snow_01.svg",      // Snow showers
-        95 => "thunderstorm-24.svg",          // Thunderstorm
-        96 | 99 => "thunder-47.svg",          // Thunderstorm with hail
-        // Some extreme / less common cases
-        61..=67 => "shower-rain-1.svg",
-        70..=79 => "medium-snow_01.svg",
-        _ => "partly-cloudy_01.svg", // Default / unknown codes
+impl<'a> JsonScanner<'a> {
+    fn positions(source: &'a str) -> HashMap<String, (usize, usize)> {
+        let mut scanner = JsonScanner {
+            chars: source.chars().peekable(),
+            line: 1,
+            column: 1,
+            positions: HashMap::new(),
+        };
+        scanner.value("$".to_string());
+        scanner.positions
     }
-}
-
-fn code_to_svg(code: u8, dim: u32) -> Result<LoadedSvg, Box<dyn std::error::Error>> {
-    let icon_file = format!("weather-icons/{}", wmo_code_to_icon(code));
-    svg_from_file(icon_file.as_str(), dim, dim, 1.0)
-}
-
-fn get_temp_range(values: &[f32]) -> (f32, f32) {
-    let min_range = 30.0;
 
-    let mut min: f32 = 999999.0;
-    let mut max: f32 = -999999.0;
-    for value in values {
-        min = min.min(*value);
-        max = max.max(*value);
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
     }
 
-    let diff = max - min;
-    // println!("DIFF {} of {}", diff, min_range);
-
-    if diff < min_range {
-        let missing = min_range - diff;
-        let missing_half = missing * 0.5;
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.bump();
+        }
+    }
 
-        min -= missing_half;
-        max += missing_half;
+    fn string(&mut self) -> Option<String> {
+        self.bump();
+        let mut out = String::new();
+        loop {
+            match self.bump()? {
+                '"' => return Some(out),
+                '\\' => out.push(self.bump()?),
+                c => out.push(c),
+            }
+        }
     }
 
-    (min, max)
+    fn value(&mut self, path: String) -> Option<()> {
+        self.skip_whitespace();
+        self.positions
+            .insert(path.clone(), (self.line, self.column));
+
+        match *self.chars.peek()? {
+            '{' => {
+                self.bump();
+                loop {
+                    self.skip_whitespace();
+                    match *self.chars.peek()? {
+                        '}' => {
+                            self.bump();
+                            return Some(());
+                        }
+                        ',' => {
+                            self.bump();
+                        }
+                        '"' => {
+                            let key = self.string()?;
+                            self.skip_whitespace();
+                            if self.bump()? != ':' {
+                                return None;
+                            }
+                            self.value(format!("{}.{}", path, key))?;
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+            '[' => {
+                self.bump();
+                let mut index = 0;
+                loop {
+                    self.skip_whitespace();
+                    match *self.chars.peek()? {
+                        ']' => {
+                            self.bump();
+                            return Some(());
+                        }
+                        ',' => {
+                            self.bump();
+                        }
+                        _ => {
+                            self.value(format!("{}[{}]", path, index))?;
+                            index += 1;
+                        }
+                    }
+                }
+            }
+            '"' => self.string().map(|_| ()),
+            _ => {
+                while self
+                    .chars
+                    .peek()
+                    .is_some_and(|c| !matches!(c, ',' | '}' | ']') && !c.is_whitespace())
+                {
+                    self.bump();
+                }
+                Some(())
+            }
+        }
+    }
 }
 
-fn draw_weather_wrapped(
-    canvas: &Canvas,
-    font_boss: &FontBoss,
-    x: i32,
-    y: i32,
-    width: i32,
-    height: i32,
-    weather: &WeatherResponse,
-    weather_age_hours: f64,
-) {
-    let too_old = weather_age_hours > 1.0;
-
-    let success = !too_old && draw_weather(canvas, font_boss, x, y, width, height, weather);
+/// Collects issues, attaching the closest known source position to each.
+struct LayoutLint {
+    positions: HashMap<String, (usize, usize)>,
+    issues: Vec<LayoutIssue>,
+}
 
-    if too_old || !success {
-        draw_text_blob(canvas, &font_boss.emoji_font, x + 20, y + 40, "😞");
+impl LayoutLint {
+    fn position_of(&self, path: &str) -> Option<(usize, usize)> {
+        let mut path = path;
+        loop {
+            if let Some(position) = self.positions.get(path) {
+                return Some(*position);
+            }
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
 
-        draw_text_blob(
-            canvas,
-            &font_boss.main_font,
-            x + 70,
-            y + 40,
-            "Problem getting weather",
-        );
+    fn push(&mut self, severity: Severity, path: &str, message: String) {
+        self.issues.push(LayoutIssue {
+            severity: severity,
+            path: path.to_string(),
+            position: self.position_of(path),
+            message: message,
+        });
     }
-}
 
-fn get_today_hi_low(weather: &WeatherResponse) -> (i32, i32) {
-    let num_daily_pts = weather.hourly.time.len().min(24);
+    fn error(&mut self, path: &str, message: String) {
+        self.push(Severity::Error, path, message);
+    }
 
-    let mut max: f32 = -9999999.0;
-    let mut min: f32 = 9999999.0;
-    for i in 0..num_daily_pts {
-        max = max.max(weather.hourly.temperature[i]);
-        min = min.min(weather.hourly.temperature[i]);
+    fn warn(&mut self, path: &str, message: String) {
+        self.push(Severity::Warning, path, message);
     }
 
-    (min.round() as i32, max.round() as i32)
-}
+    /// Checks the raw JSON node by node, so every bad node is reported
+    /// rather than just the first one serde trips over. `parent` is the
+    /// enclosing node's type, None at the root.
+    fn check_schema(&mut self, value: &Value, path: &str, parent: Option<&str>) {
+        let Some(node) = value.as_object() else {
+            self.error(path, "Expected a layout node object".to_string());
+            return;
+        };
 
-fn draw_weather(
-    canvas: &Canvas,
-    font_boss: &FontBoss,
-    x: i32,
-    y: i32,
-    width: i32,
-    _height: i32,
-    weather: &WeatherResponse,
-) -> bool {
-    println!(" code {}", weather.current.weather_code);
+        let node_type = match node.get("type") {
+            Some(Value::String(node_type)) => node_type.as_str(),
+            Some(_) => {
+                self.error(
+                    &format!("{}.type", path),
+                    "\"type\" must be a string".to_string(),
+                );
+                return;
+            }
+            None => {
+                self.error(path, "Missing \"type\"".to_string());
+                return;
+            }
+        };
+        if !LAYOUT_NODE_TYPES.contains(&node_type) {
+            self.error(
+                &format!("{}.type", path),
+                format!(
+                    "Unknown node type '{}', expected one of {}",
+                    node_type,
+                    LAYOUT_NODE_TYPES.join(", ")
+                ),
+            );
+        }
 
-    let svg = code_to_svg(weather.current.weather_code, 75);
+        match node.get("size") {
+            Some(size) => {
+                if let Err(e) = serde_json::from_value::<Size>(size.clone()) {
+                    self.error(&format!("{}.size", path), e.to_string());
+                }
+            }
+            None if parent == Some("container") => self.error(path, "Missing \"size\"".to_string()),
+            None => {}
+        }
 
-    canvas.draw_image(
-        &svg.unwrap().image,
-        (x as f32 + 15.0, y as f32 + 10.0),
-        None,
-    );
+        if let Some(dither) = node.get("dither")
+            && let Err(e) = serde_json::from_value::<DitherMethod>(dither.clone())
+        {
+            self.error(&format!("{}.dither", path), e.to_string());
+        }
 
-    let mini_font = font_boss.load_font(20.0);
-    let med_font = font_boss.load_font(35.0);
-    let mega_font = font_boss.load_font(100.0);
-    let now_offset = 30;
+        if let Some(when) = node.get("when") {
+            match serde_json::from_value::<Condition>(when.clone()) {
+                Ok(condition) => self.check_condition(&condition, &format!("{}.when", path)),
+                Err(e) => self.error(&format!("{}.when", path), e.to_string()),
+            }
+            if parent.is_none() {
+                self.warn(
+                    &format!("{}.when", path),
+                    "The root node is always drawn, so \"when\" is ignored".to_string(),
+                );
+            }
+        }
 
-    let cur_temp_str = format!("{}°", weather.current.temperature.round());
-    let cur_temp_str_w = mega_font.measure_str(&cur_temp_str, None).0;
-    draw_text_blob(
-        canvas,
-        &mega_font,
-        x + 105,
-        y + now_offset + 45,
-        &cur_temp_str,
-    );
+        if let Some(options) = node.get("options") {
+            let options_path = format!("{}.options", path);
+            let options = options.clone();
+            let parsed = match node_type {
+                "date" => serde_json::from_value::<DateOptions>(options).map(|_| ()),
+                "todo" => serde_json::from_value::<TodoOptions>(options).map(|_| ()),
+                "hline" | "vline" => serde_json::from_value::<LineOptions>(options).map(|_| ()),
+                "weather" => serde_json::from_value::<WeatherOptions>(options).map(|_| ()),
+                "allowance" => serde_json::from_value::<AllowanceOptions>(options).map(|_| ()),
+                "countdown" => serde_json::from_value::<CountdownOptions>(options).map(|_| ()),
+                "battery" => serde_json::from_value::<BatteryOptions>(options).map(|_| ()),
+                "verse" => serde_json::from_value::<VerseOptions>(options).map(|_| ()),
+                "container" | "grid" | "overlay" => {
+                    self.warn(
+                        &options_path,
+                        format!("'{}' nodes have no options", node_type),
+                    );
+                    Ok(())
+                }
+                _ => Ok(()),
+            };
+            if let Err(e) = parsed {
+                self.error(&options_path, e.to_string());
+            }
+        }
 
-    let hilo = get_today_hi_low(&weather);
+        for key in ["margin", "padding"] {
+            if let Some(edges) = node.get(key)
+                && let Err(e) = serde_json::from_value::<Edges>(edges.clone())
+            {
+                self.error(&format!("{}.{}", path, key), e.to_string());
+            }
+        }
 
-    let hilo_start = x + cur_temp_str_w as i32 + 120;
-    let hi_temp_str = format!("{}°", hilo.1);
-    let hi_temp_str_w = med_font.measure_str(&hi_temp_str, None).0;
-    let lo_temp_str = format!("{}°", hilo.0);
-    let lo_temp_str_w = med_font.measure_str(&lo_temp_str, None).0;
-    let hilo_w = hi_temp_str_w.max(lo_temp_str_w);
-    let hilo_w_h = hi_temp_str_w.max(lo_temp_str_w) as i32 / 2;
+        for key in ["min", "max"] {
+            if let Some(bound) = node.get(key)
+                && let Err(e) = serde_json::from_value::<u32>(bound.clone())
+            {
+                self.error(&format!("{}.{}", path, key), e.to_string());
+            }
+        }
 
-    draw_text_blob_with_color(
-        canvas,
-        &med_font,
-        hilo_start + hilo_w_h,
-        y + now_offset - 5 + 12,
-        &hi_temp_str,
-        Color::BLACK,
-        0.5,
-    );
+        if let Some(cell) = node.get("cell") {
+            if let Err(e) = serde_json::from_value::<GridCell>(cell.clone()) {
+                self.error(&format!("{}.cell", path), e.to_string());
+            } else if parent != Some("grid") {
+                self.warn(
+                    &format!("{}.cell", path),
+                    "\"cell\" only applies inside a grid".to_string(),
+                );
+            }
+        }
 
-    draw_text_blob_with_color(
-        canvas,
-        &med_font,
-        hilo_start + hilo_w_h,
-        y + now_offset + 45,
-        &lo_temp_str,
-        Color::BLACK,
-        0.5,
-    );
+        if let Some(anchor) = node.get("anchor")
+            && let Err(e) = serde_json::from_value::<Anchor>(anchor.clone())
+        {
+            self.error(&format!("{}.anchor", path), e.to_string());
+        }
+        for key in ["width", "height"] {
+            if let Some(size) = node.get(key)
+                && let Err(e) = serde_json::from_value::<Size>(size.clone())
+            {
+                self.error(&format!("{}.{}", path, key), e.to_string());
+            }
+        }
+        if parent != Some("overlay") {
+            for key in ["anchor", "width", "height"] {
+                if node.contains_key(key) {
+                    self.warn(
+                        &format!("{}.{}", path, key),
+                        format!("\"{}\" only applies inside an overlay", key),
+                    );
+                }
+            }
+        }
 
-    {
-        let y = (y + now_offset - 5 + 20) as f32;
-        let start = Point::new(hilo_start as f32, y);
-        let end = Point::new(hilo_start as f32 + hilo_w, y);
-        draw_colored_line(canvas, start, end, Color::BLACK);
-    }
+        match node_type {
+            "container" => {
+                if let Some(align) = node.get("align")
+                    && let Err(e) = serde_json::from_value::<Align>(align.clone())
+                {
+                    self.error(&format!("{}.align", path), e.to_string());
+                }
 
-    draw_text_blob_with_color(
-        canvas,
-        &font_boss.main_font,
-        x + width + 5,
-        y + now_offset + 5,
-        &format!(
-            "Feels like {}°",
-            weather.current.apparent_temperature.round()
-        ),
-        Color::BLACK,
-        1.0,
-    );
+                match node.get("split") {
+                    Some(split) => {
+                        if let Err(e) = serde_json::from_value::<SplitDirection>(split.clone()) {
+                            self.error(&format!("{}.split", path), e.to_string());
+                        }
+                    }
+                    None => self.error(path, "Container is missing \"split\"".to_string()),
+                }
+            }
+            "grid" => {
+                for key in ["rows", "columns"] {
+                    match node.get(key) {
+                        Some(Value::Array(tracks)) if !tracks.is_empty() => {
+                            for (i, track) in tracks.iter().enumerate() {
+                                if let Err(e) = serde_json::from_value::<Size>(track.clone()) {
+                                    self.error(&format!("{}.{}[{}]", path, key, i), e.to_string());
+                                }
+                            }
+                        }
+                        Some(_) => self.error(
+                            &format!("{}.{}", path, key),
+                            format!("\"{}\" must be a non-empty array of sizes", key),
+                        ),
+                        None => self.error(path, format!("Grid is missing \"{}\"", key)),
+                    }
+                }
 
-    draw_text_blob_with_color(
-        canvas,
-        &font_boss.main_font,
-        x + width + 5,
-        y + now_offset + 35,
-        &format!("Humidity {}%", weather.current.relative_humidity),
-        Color::BLACK,
-        1.0,
-    );
+                if let Some(gap) = node.get("gap")
+                    && let Err(e) = serde_json::from_value::<i32>(gap.clone())
+                {
+                    self.error(&format!("{}.gap", path), e.to_string());
+                }
+            }
+            "overlay" => {}
+            _ => return,
+        }
 
-    let today_offset = 110;
-    let hourly_height = 80;
+        match node.get("entries") {
+            Some(Value::Array(entries)) => {
+                for (i, entry) in entries.iter().enumerate() {
+                    self.check_schema(entry, &format!("{}.entries[{}]", path, i), Some(node_type));
+                }
+            }
+            Some(_) => self.error(
+                &format!("{}.entries", path),
+                "\"entries\" must be an array".to_string(),
+            ),
+            None => self.error(path, "Container is missing \"entries\"".to_string()),
+        }
+    }
 
-    // 1) Get current time (timezone-aware)
-    let now_local: DateTime<Local> = Local::now();
-    let now_utc: DateTime<Utc> = Utc::now();
-    let naive_local: NaiveDateTime = now_local.naive_local();
+    /// Resolves the tree the same way the renderer does, with every `when`
+    /// holding, and flags children that overflow, are left with no space,
+    /// or leave space unclaimed.
+    fn check_geometry(&mut self, node: &LayoutNode, path: &str, width: i32, height: i32) {
+        let layout = node.box_layout();
+        if let (Some(min), Some(max)) = (layout.min, layout.max)
+            && min > max
+        {
+            self.error(
+                &format!("{}.min", path),
+                format!("min {}px is larger than max {}px", min, max),
+            );
+        }
 
-    println!("local now = {}", now_local);
-    println!("utc   now = {}", now_utc);
+        let [_, (_, _, width, height)] = node.boxes(0, 0, width, height);
+        if width <= 0 || height <= 0 {
+            self.warn(
+                path,
+                format!(
+                    "Content box resolves to {}x{} and won't be drawn",
+                    width, height
+                ),
+            );
+        }
 
-    println!("~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~");
+        let container = match node {
+            LayoutNode::Container(container) => container,
+            LayoutNode::Grid(grid) => return self.check_grid(grid, path, width, height),
+            LayoutNode::Overlay(overlay) => {
+                for (i, child) in overlay.entries.iter().enumerate() {
+                    let (_, _, child_width, child_height) = overlay_rect(child, width, height);
+                    let child_path = format!("{}.entries[{}]", path, i);
+                    self.check_geometry(child, &child_path, child_width, child_height);
+                }
+                return;
+            }
+            _ => return,
+        };
 
-    let mut opt_hourly_start_index: Option<usize> = None;
-    for i in 0..weather.hourly.time.len() {
-        // println!("dsfdsf  {}", weather.hourly.time[i]);
+        let (split_dim_pix, axis) = match container.split {
+            SplitDirection::Horizontal => (width, "wide"),
+            SplitDirection::Vertical => (height, "tall"),
+        };
+        let entries_path = format!("{}.entries", path);
 
-        // Parse as a naive datetime (no timezone)
-        let dt = NaiveDateTime::parse_from_str(&weather.hourly.time[i], "%Y-%m-%dT%H:%M")
-            .expect("Failed to parse datetime");
+        if container.entries.is_empty() {
+            self.warn(&entries_path, "Container has no entries".to_string());
+            return;
+        }
 
-        if naive_local < dt {
-            break;
+        let fixed_sum: i32 = container
+            .entries
+            .iter()
+            .filter_map(|child| {
+                fixed_from(child.size(), split_dim_pix).map(|px| child.box_layout().clamp(px))
+            })
+            .sum::<f64>() as i32;
+        let has_units = container
+            .entries
+            .iter()
+            .any(|child| matches!(child.size(), Size::Unit(_)));
+
+        if fixed_sum > split_dim_pix.max(0) {
+            self.error(
+                &entries_path,
+                format!(
+                    "Fixed children need {}px but the container is only {}px {}",
+                    fixed_sum, split_dim_pix, axis
+                ),
+            );
+        } else if !has_units && fixed_sum < split_dim_pix && container.align == Align::Start {
+            self.warn(
+                &entries_path,
+                format!(
+                    "No \"u\" children, so {}px of the {}px {} container is left empty",
+                    split_dim_pix - fixed_sum,
+                    split_dim_pix,
+                    axis
+                ),
+            );
         }
 
-        opt_hourly_start_index = Some(i);
+        let entries: Vec<&LayoutNode> = container.entries.iter().collect();
+        let (_, sizes) = split_sizes(&entries, container.align, split_dim_pix);
+        for (i, (child, size)) in container.entries.iter().zip(sizes).enumerate() {
+            let child_path = format!("{}[{}]", entries_path, i);
+            match container.split {
+                SplitDirection::Horizontal => self.check_geometry(child, &child_path, size, height),
+                SplitDirection::Vertical => self.check_geometry(child, &child_path, width, size),
+            }
+        }
     }
 
-    if opt_hourly_start_index.is_none() {
-        return false;
-    }
+    /// Flags `stale` feeds that don't exist and clauses that can never hold.
+    fn check_condition(&mut self, condition: &Condition, path: &str) {
+        if let Some(feed) = &condition.stale
+            && !AllData::FEEDS.contains(&feed.as_str())
+        {
+            self.error(
+                &format!("{}.stale", path),
+                format!(
+                    "Unknown feed '{}', expected one of {}",
+                    feed,
+                    AllData::FEEDS.join(", ")
+                ),
+            );
+        }
 
-    let hourly_start_index = opt_hourly_start_index.unwrap();
+        if let Some(weekdays) = &condition.weekdays
+            && weekdays.is_empty()
+        {
+            self.warn(
+                &format!("{}.weekdays", path),
+                "No weekdays listed, so the node is never shown".to_string(),
+            );
+        }
 
-    let n_forecast_hours = 23;
-    let hourly_stop_index = (hourly_start_index + n_forecast_hours).min(weather.hourly.time.len());
+        if let Some(DateRange::Dates(from, to)) = condition.dates
+            && from > to
+        {
+            self.warn(
+                &format!("{}.dates", path),
+                format!("{} is after {}, so the node is never shown", from, to),
+            );
+        }
 
-    let hourly_x_start = x + 50;
-    let hourly_width = width - 47;
-    let num_hours = hourly_stop_index - hourly_start_index;
+        for (i, clause) in condition.any.iter().enumerate() {
+            self.check_condition(clause, &format!("{}.any[{}]", path, i));
+        }
+        if let Some(clause) = &condition.not {
+            self.check_condition(clause, &format!("{}.not", path));
+        }
+    }
 
-    println!("the thing starts at {}", hourly_start_index);
-    println!("the thing stops at {}", hourly_stop_index);
-    println!("num_hours {}", num_hours);
+    /// Flags fixed tracks that overflow the grid, children with nowhere to
+    /// go and explicit cells that overlap, then checks each child in its
+    /// cell.
+    fn check_grid(&mut self, grid: &GridNode, path: &str, width: i32, height: i32) {
+        for (key, tracks, dim_pix, axis) in [
+            ("columns", &grid.columns, width, "wide"),
+            ("rows", &grid.rows, height, "tall"),
+        ] {
+            let gaps = grid.gap * (tracks.len() as i32 - 1).max(0);
+            let fixed_sum = tracks
+                .iter()
+                .filter_map(|track| fixed_from(track, dim_pix - gaps))
+                .sum::<f64>() as i32;
+            if fixed_sum + gaps > dim_pix.max(0) {
+                self.error(
+                    &format!("{}.{}", path, key),
+                    format!(
+                        "Fixed {} and gaps need {}px but the grid is only {}px {}",
+                        key,
+                        fixed_sum + gaps,
+                        dim_pix,
+                        axis
+                    ),
+                );
+            }
+        }
 
-    let hourly_slot_width = hourly_width as f32 / (num_hours - 1) as f32;
+        let entries_path = format!("{}.entries", path);
+        if grid.entries.is_empty() {
+            self.warn(&entries_path, "Grid has no entries".to_string());
+            return;
+        }
 
-    println!("hourly_x_start {}", hourly_x_start);
-    println!("hourly_slot_width {}", hourly_slot_width);
+        let entries: Vec<&LayoutNode> = grid.entries.iter().collect();
+        let (rows, columns) = (grid.rows.len(), grid.columns.len());
+        let mut covered = vec![0usize; rows * columns];
+        for (i, (child, placement)) in entries
+            .iter()
+            .zip(grid_placements(grid, &entries))
+            .enumerate()
+        {
+            let child_path = format!("{}[{}]", entries_path, i);
+            match (child.box_layout().cell, placement) {
+                (Some(cell), None) => self.error(
+                    &format!("{}.cell", child_path),
+                    format!(
+                        "Cell at row {}, column {} spanning {}x{} doesn't fit the {}x{} grid",
+                        cell.row, cell.column, cell.row_span, cell.column_span, rows, columns
+                    ),
+                ),
+                (None, None) => self.error(
+                    &child_path,
+                    format!("No free cell left in the {}x{} grid", rows, columns),
+                ),
+                (Some(cell), Some(_)) => {
+                    let mut overlaps = false;
+                    for row in cell.row..cell.row + cell.row_span {
+                        for column in cell.column..cell.column + cell.column_span {
+                            covered[row * columns + column] += 1;
+                            overlaps |= covered[row * columns + column] > 1;
+                        }
+                    }
+                    if overlaps {
+                        self.warn(
+                            &format!("{}.cell", child_path),
+                            "Cell overlaps an earlier entry; use an overlay to stack widgets"
+                                .to_string(),
+                        );
+                    }
+                }
+                (None, Some(_)) => {}
+            }
+        }
 
-    let mut precip_points: Vec<f32> = Vec::new();
+        for (i, (child, rect)) in grid
+            .entries
+            .iter()
+            .zip(grid_rects(grid, &entries, width, height))
+            .enumerate()
+        {
+            if let Some((_, _, cell_width, cell_height)) = rect {
+                let child_path = format!("{}[{}]", entries_path, i);
+                self.check_geometry(child, &child_path, cell_width, cell_height);
+            }
+        }
+    }
+}
+
+/// Everything wrong with a layout file when drawn on the resolved panel.
+fn lint_layout(source: &str, overrides: &LayoutDisplay) -> Vec<LayoutIssue> {
+    let mut lint = LayoutLint {
+        positions: JsonScanner::positions(source),
+        issues: Vec::new(),
+    };
+
+    let value: Value = match serde_json::from_str(source) {
+        Ok(value) => value,
+        Err(e) => {
+            lint.issues.push(LayoutIssue {
+                severity: Severity::Error,
+                path: "$".to_string(),
+                position: Some((e.line(), e.column())),
+                message: e.to_string(),
+            });
+            return lint.issues;
+        }
+    };
+
+    lint.check_schema(&value, "$", None);
+    if lint
+        .issues
+        .iter()
+        .any(|issue| issue.severity == Severity::Error)
+    {
+        return lint.issues;
+    }
+
+    let root: LayoutNode = match serde_json::from_value(value.clone()) {
+        Ok(root) => root,
+        Err(e) => {
+            lint.error("$", e.to_string());
+            return lint.issues;
+        }
+    };
+    let panel = match serde_json::from_value::<LayoutHeader>(value)
+        .map_err(|e| e.to_string())
+        .and_then(|header| PanelConfig::resolve(overrides, &header.display))
+    {
+        Ok(panel) => panel,
+        Err(e) => {
+            lint.error("$.display", e);
+            return lint.issues;
+        }
+    };
+
+    if !root.is_container() {
+        lint.error(
+            "$.type",
+            "The root node must be a container, grid or overlay".to_string(),
+        );
+        return lint.issues;
+    }
+
+    let (width, height) = panel.canvas_size();
+    lint.check_geometry(&root, "$", width as i32, height as i32);
+
+    lint.issues
+}
+
+// Draws a smooth Catmull-Rom spline through the points
+// and fills the area under it down to the baseline.
+fn fill_catmull_rom_area(canvas: &RenderCanvas, points: &[Point], baseline_y: f32) {
+    if points.len() < 2 {
+        return;
+    }
+
+    // --- Create the fill path ---
+    let mut fill_path = Path::new();
+    fill_path.move_to(Point::new(points[0].x, baseline_y)); // baseline start
+    fill_path.line_to(points[0]); // move up to first point
+
+    // Build Catmull-Rom spline
+    for i in 0..points.len() - 1 {
+        let p0 = if i == 0 { points[0] } else { points[i - 1] };
+        let p1 = points[i];
+        let p2 = points[i + 1];
+        let p3 = if i + 2 < points.len() {
+            points[i + 2]
+        } else {
+            points[points.len() - 1]
+        };
+
+        let c1 = Point::new(p1.x + (p2.x - p0.x) / 6.0, p1.y + (p2.y - p0.y) / 6.0);
+        let c2 = Point::new(p2.x - (p3.x - p1.x) / 6.0, p2.y - (p3.y - p1.y) / 6.0);
+
+        fill_path.cubic_to(c1, c2, p2);
+    }
+
+    // Line down to baseline at last x
+    fill_path.line_to(Point::new(points[points.len() - 1].x, baseline_y));
+    // Close path (connects back to baseline start)
+    fill_path.close();
+
+    let left = points.iter().map(|p| p.x).fold(f32::MAX, f32::min);
+    let right = points.iter().map(|p| p.x).fold(f32::MIN, f32::max);
+    let top = points.iter().map(|p| p.y).fold(baseline_y, f32::min);
+    canvas.mark_image(Rect::new(left, top, right, baseline_y));
+
+    // --- Fill area ---
+    let mut paint_fill = Paint::default();
+    paint_fill.set_color(Color::from_rgb(220, 220, 220));
+    paint_fill.set_anti_alias(true);
+    paint_fill.set_style(PaintStyle::Fill);
+    canvas.draw_path(&fill_path, &paint_fill);
+
+    // --- Stroke only the curve ---
+    let mut curve_path = Path::new();
+    curve_path.move_to(points[0]);
+
+    for i in 0..points.len() - 1 {
+        let p0 = if i == 0 { points[0] } else { points[i - 1] };
+        let p1 = points[i];
+        let p2 = points[i + 1];
+        let p3 = if i + 2 < points.len() {
+            points[i + 2]
+        } else {
+            points[points.len() - 1]
+        };
+
+        let c1 = Point::new(p1.x + (p2.x - p0.x) / 6.0, p1.y + (p2.y - p0.y) / 6.0);
+        let c2 = Point::new(p2.x - (p3.x - p1.x) / 6.0, p2.y - (p3.y - p1.y) / 6.0);
+
+        curve_path.cubic_to(c1, c2, p2);
+    }
+
+    let mut paint_curve = Paint::default();
+    paint_curve.set_color(Color::BLACK);
+    paint_curve.set_style(PaintStyle::Stroke);
+    paint_curve.set_stroke_width(2.0);
+    paint_curve.set_anti_alias(true);
+
+    canvas.draw_path(&curve_path, &paint_curve);
+}
+
+fn draw_filled_circle(canvas: &RenderCanvas, center: Point, radius: f32, color: Color) {
+    let mut paint = Paint::default();
+    paint.set_color(color); // red color
+    paint.set_anti_alias(true); // smooth edges
+    paint.set_style(PaintStyle::Fill); // fill, not stroke
+
+    canvas.draw_circle(center, radius, &paint);
+}
+
+fn draw_hourly(
+    canvas: &RenderCanvas,
+    mini_font: &Font,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    values: &[f32],
+    symbol: &str,
+    range: (f32, f32),
+) {
+    // draw_rect_thing(canvas, x, y, width, height);
+
+    let dp_width = (width as f32) / (values.len() - 1) as f32;
+
+    let (min, max) = range;
+
+    let vsize = max - min;
+
+    println!("min {} max {} vsize {}", min, max, vsize);
+
+    let graph_base = 10.0;
+    let graph_offset = y as f32 + 5.0;
+    let graph_height = (height - 10) as f32 - graph_base;
+    let mut temp_points: Vec<Point> = Vec::with_capacity(values.len());
+    for i in 0..values.len() {
+        let val = (values[i] - min) / vsize;
+
+        let px = x as f32 + i as f32 * dp_width;
+        let py = graph_offset + (1.0 - val) * graph_height as f32;
+        temp_points.push(Point::new(px, py));
+
+        // draw_filled_circle(canvas, Point::new(px, py), 5.0);
+    }
+    //draw_catmull_rom_curve(canvas, &temp_points);
+    fill_catmull_rom_area(
+        canvas,
+        &temp_points,
+        graph_offset + graph_height + graph_base,
+    );
+
+    for i in 0..values.len() {
+        let pct = i as f32 / (values.len() - 1) as f32;
+        // let val = (values[i] - min) / vsize;
+        let px = x as f32 + i as f32 * dp_width;
+        // let py = graph_offset + val * graph_height as f32;
+        if i % 2 == 0 {
+            draw_text_blob_with_color(
+                canvas,
+                &mini_font,
+                px as i32,
+                (graph_offset + graph_height) as i32 + 28,
+                &format!("{}{}", values[i].round(), symbol),
+                Color::from_rgb(128, 128, 128),
+                pct,
+            );
+        }
+    }
+}
+
+fn draw_box_with_gradient(
+    canvas: &RenderCanvas,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    top_color: Color,
+    bottom_color: Color,
+) {
+    let margin = 0;
+
+    let rect = Rect::from_xywh(
+        (x + margin) as f32,
+        (y + margin) as f32,
+        (width - margin * 2) as f32,
+        (height - margin * 2) as f32,
+    );
+    let rrect = RRect::new_rect_xy(rect, 8.0, 8.0);
+    canvas.mark_image(rect);
+
+    // --- Gradient fill ---
+    let start_color = Color4f::from(top_color);
+    let end_color = Color4f::from(bottom_color);
+
+    // make a slice explicitly
+    let colors_slice: &[Color4f] = &[start_color, end_color];
+
+    let shader = gradient_shader::linear(
+        (
+            Point::new(rect.left, rect.top),
+            Point::new(rect.left, rect.bottom),
+        ), // tuple of points
+        colors_slice, // explicit slice
+        None,
+        TileMode::Clamp,
+        None,
+        None,
+    );
+
+    let mut fill_paint = Paint::default();
+    fill_paint.set_anti_alias(true);
+    fill_paint.set_style(PaintStyle::Fill);
+    fill_paint.set_shader(shader);
+
+    canvas.draw_rrect(rrect, &fill_paint);
+
+    // --- Black outline ---
+    let mut stroke_paint = Paint::default();
+    stroke_paint.set_color(Color::BLACK);
+    stroke_paint.set_anti_alias(true);
+    stroke_paint.set_style(PaintStyle::Stroke);
+    stroke_paint.set_stroke_width(2.0);
+
+    canvas.draw_rrect(rrect, &stroke_paint);
+}
+
+fn draw_temp_gradient(canvas: &RenderCanvas, x: i32, y: i32, width: i32, height: i32) {
+    draw_box_with_gradient(
+        canvas,
+        x,
+        y,
+        width,
+        height,
+        Color::from_rgb(180, 180, 180),
+        Color::from_rgb(240, 240, 240),
+    );
+}
+
+fn wmo_code_to_icon(code: u8) -> &'static str {
+    match code {
+        0 => "sunny-29.svg",                  // Clear sky
+        1 | 2 | 3 => "partly-cloudy-5.svg",   // Mainly clear, partly cloudy, overcast
+        45 | 48 => "fog-85.svg",              // Fog / depositing rime fog
+        51 | 53 | 55 => "light-rain-90.svg",  // Drizzle
+        56 | 57 => "sleet_03.svg",            // Freezing drizzle
+        61 | 63 | 65 => "shower-rain-1.svg",  // Rain showers
+        66 | 67 => "sleet_04.svg",            // Freezing rain
+        71 | 73 | 75 => "slight-snow_01.svg", // Snow fall
+        77 => "slight-snow.svg",              // Snow grains
+        80 | 81 | 82 => "shower-rain-1.svg",  // Rain showers
+        85 | 86 => "medium-snow_01.svg",      // Snow showers
+        95 => "thunderstorm-24.svg",          // Thunderstorm
+        96 | 99 => "thunder-47.svg",          // Thunderstorm with hail
+        // Some extreme / less common cases
+        61..=67 => "shower-rain-1.svg",
+        70..=79 => "medium-snow_01.svg",
+        _ => "partly-cloudy_01.svg", // Default / unknown codes
+    }
+}
+
+fn draw_icon(canvas: &RenderCanvas, image: &Image, left: f32, top: f32) {
+    canvas.draw_image(image, (left, top), None);
+    canvas.mark_image(Rect::from_xywh(
+        left,
+        top,
+        image.width() as f32,
+        image.height() as f32,
+    ));
+}
+
+fn code_to_svg(code: u8, dim: u32) -> Result<LoadedSvg, Box<dyn std::error::Error>> {
+    let icon_file = format!("weather-icons/{}", wmo_code_to_icon(code));
+    svg_from_file(icon_file.as_str(), dim, dim, 1.0)
+}
+
+fn get_temp_range(values: &[f32]) -> (f32, f32) {
+    let min_range = 30.0;
+
+    let mut min: f32 = 999999.0;
+    let mut max: f32 = -999999.0;
+    for value in values {
+        min = min.min(*value);
+        max = max.max(*value);
+    }
+
+    let diff = max - min;
+    // println!("DIFF {} of {}", diff, min_range);
+
+    if diff < min_range {
+        let missing = min_range - diff;
+        let missing_half = missing * 0.5;
+
+        min -= missing_half;
+        max += missing_half;
+    }
+
+    (min, max)
+}
+
+fn draw_weather_wrapped(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    weather: &WeatherResponse,
+    now: NaiveDateTime,
+    options: &WeatherOptions,
+) {
+    if let Err(reason) = draw_weather(
+        canvas, font_boss, x, y, width, height, weather, now, options,
+    ) {
+        draw_feed_error(canvas, font_boss, x, y, width, "weather", &reason);
+    }
+}
+
+fn get_today_hi_low(weather: &WeatherResponse) -> (i32, i32) {
+    let num_daily_pts = weather.hourly.time.len().min(24);
+
+    let mut max: f32 = -9999999.0;
+    let mut min: f32 = 9999999.0;
+    for i in 0..num_daily_pts {
+        max = max.max(weather.hourly.temperature[i]);
+        min = min.min(weather.hourly.temperature[i]);
+    }
+
+    (min.round() as i32, max.round() as i32)
+}
+
+/// The forecast's hourly times and daily dates, parsed once, after checking
+/// every per-hour and per-day array lines up with them.
+fn forecast_times(
+    weather: &WeatherResponse,
+) -> Result<(Vec<NaiveDateTime>, Vec<NaiveDate>), String> {
+    fn check_lengths(what: &str, times: usize, arrays: &[(&str, usize)]) -> Result<(), String> {
+        match arrays.iter().find(|(_, len)| *len != times) {
+            Some((name, len)) => Err(format!(
+                "{} {} has {} entries for {} times",
+                what, name, len, times
+            )),
+            None => Ok(()),
+        }
+    }
+
+    let hourly = &weather.hourly;
+    check_lengths(
+        "Hourly",
+        hourly.time.len(),
+        &[
+            ("temperature_2m", hourly.temperature.len()),
+            ("weather_code", hourly.weather_code.len()),
+            ("precipitation", hourly.precipitation.len()),
+            (
+                "precipitation_probability",
+                hourly.precipitation_probability.len(),
+            ),
+        ],
+    )?;
+    let hourly_times = hourly
+        .time
+        .iter()
+        .map(|time| {
+            NaiveDateTime::parse_from_str(time, "%Y-%m-%dT%H:%M")
+                .map_err(|e| format!("Bad hourly time '{}': {}", time, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let Some(daily) = &weather.daily else {
+        return Ok((hourly_times, Vec::new()));
+    };
+    check_lengths(
+        "Daily",
+        daily.time.len(),
+        &[
+            ("weather_code", daily.weather_code.len()),
+            ("temperature_2m_max", daily.temperature_max.len()),
+            ("temperature_2m_min", daily.temperature_min.len()),
+            (
+                "precipitation_probability_max",
+                daily.precipitation_probability.len(),
+            ),
+        ],
+    )?;
+    let daily_dates = daily
+        .time
+        .iter()
+        .map(|date| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .map_err(|e| format!("Bad daily date '{}': {}", date, e))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok((hourly_times, daily_dates))
+}
+
+fn draw_weather(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    x: i32,
+    y: i32,
+    width: i32,
+    _height: i32,
+    weather: &WeatherResponse,
+    now: NaiveDateTime,
+    options: &WeatherOptions,
+) -> Result<(), String> {
+    println!(" code {}", weather.current.weather_code);
+
+    let (hourly_times, daily_dates) = forecast_times(weather)?;
+
+    // Every icon is rasterized before anything is drawn, so a bad one leaves
+    // the placeholder on a clean box rather than over half a forecast
+    let icon_error = |code: u8, e: Box<dyn std::error::Error>| {
+        format!("No icon for weather code {}: {}", code, e)
+    };
+    let svg_width = 25;
+    let icon = font_boss
+        .weather_icon(weather.current.weather_code, 75)
+        .map_err(|e| icon_error(weather.current.weather_code, e))?;
+    let daily_icons = match &weather.daily {
+        Some(daily) => (0..daily.time.len().min(options.daily_columns))
+            .map(|i| {
+                let code = daily.weather_code[i];
+                font_boss
+                    .weather_icon(code, svg_width)
+                    .map_err(|e| icon_error(code, e))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        None => Vec::new(),
+    };
+
+    draw_icon(canvas, &icon, x as f32 + 15.0, y as f32 + 10.0);
+
+    let mini_font = font_boss.load_font(20.0);
+    let med_font = font_boss.load_font(35.0);
+    let mega_font = font_boss.load_font(100.0);
+    let font = font_boss.load_option(&options.font);
+    let now_offset = 30;
+
+    let cur_temp_str = format!("{}°", weather.current.temperature.round());
+    let cur_temp_str_w = mega_font.measure_str(&cur_temp_str, None).0;
+    draw_text_blob_with_color(
+        canvas,
+        &mega_font,
+        x + 105,
+        y + now_offset + 45,
+        &cur_temp_str,
+        options.color,
+        0.0,
+    );
+
+    let hilo = get_today_hi_low(&weather);
+
+    let hilo_start = x + cur_temp_str_w as i32 + 120;
+    let hi_temp_str = format!("{}°", hilo.1);
+    let hi_temp_str_w = med_font.measure_str(&hi_temp_str, None).0;
+    let lo_temp_str = format!("{}°", hilo.0);
+    let lo_temp_str_w = med_font.measure_str(&lo_temp_str, None).0;
+    let hilo_w = hi_temp_str_w.max(lo_temp_str_w);
+    let hilo_w_h = hi_temp_str_w.max(lo_temp_str_w) as i32 / 2;
+
+    draw_text_blob_with_color(
+        canvas,
+        &med_font,
+        hilo_start + hilo_w_h,
+        y + now_offset - 5 + 12,
+        &hi_temp_str,
+        options.color,
+        0.5,
+    );
+
+    draw_text_blob_with_color(
+        canvas,
+        &med_font,
+        hilo_start + hilo_w_h,
+        y + now_offset + 45,
+        &lo_temp_str,
+        options.color,
+        0.5,
+    );
+
+    {
+        let y = (y + now_offset - 5 + 20) as f32;
+        let start = Point::new(hilo_start as f32, y);
+        let end = Point::new(hilo_start as f32 + hilo_w, y);
+        draw_colored_line(canvas, start, end, options.color);
+    }
+
+    draw_text_blob_with_color(
+        canvas,
+        &font,
+        x + width + 5,
+        y + now_offset + 5,
+        &format!(
+            "Feels like {}°",
+            weather.current.apparent_temperature.round()
+        ),
+        options.color,
+        1.0,
+    );
+
+    draw_text_blob_with_color(
+        canvas,
+        &font,
+        x + width + 5,
+        y + now_offset + 35,
+        &format!("Humidity {}%", weather.current.relative_humidity),
+        options.color,
+        1.0,
+    );
+
+    let today_offset = 110;
+    let hourly_height = 80;
+
+    // 1) The time being rendered, as wall-clock time where the forecast is
+    let naive_local: NaiveDateTime = now;
+
+    println!("local now = {}", naive_local);
+
+    println!("~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~~");
+
+    let mut opt_hourly_start_index: Option<usize> = None;
+    for (i, dt) in hourly_times.iter().enumerate() {
+        // println!("dsfdsf  {}", weather.hourly.time[i]);
+
+        if naive_local < *dt {
+            break;
+        }
+
+        opt_hourly_start_index = Some(i);
+    }
+
+    if opt_hourly_start_index.is_none() {
+        return Err("The hourly forecast starts after now".to_string());
+    }
+
+    let hourly_start_index = opt_hourly_start_index.unwrap();
+
+    let n_forecast_hours = options.forecast_hours.max(2);
+    let hourly_stop_index = (hourly_start_index + n_forecast_hours).min(weather.hourly.time.len());
+
+    let hourly_x_start = x + 50;
+    let hourly_width = width - 47;
+    let num_hours = hourly_stop_index - hourly_start_index;
+
+    println!("the thing starts at {}", hourly_start_index);
+    println!("the thing stops at {}", hourly_stop_index);
+    println!("num_hours {}", num_hours);
+
+    let hourly_slot_width = hourly_width as f32 / (num_hours - 1) as f32;
+
+    println!("hourly_x_start {}", hourly_x_start);
+    println!("hourly_slot_width {}", hourly_slot_width);
+
+    let mut precip_points: Vec<f32> = Vec::new();
     let mut temp_points: Vec<f32> = Vec::new();
 
-    for i in hourly_start_index..hourly_stop_index {
-        temp_points.push(weather.hourly.temperature[i]);
-        precip_points.push(weather.hourly.precipitation_probability[i] as f32);
+    for i in hourly_start_index..hourly_stop_index {
+        temp_points.push(weather.hourly.temperature[i]);
+        precip_points.push(weather.hourly.precipitation_probability[i] as f32);
+
+        let index = i - hourly_start_index;
+        // let pct = index as f32 / (num_hours - 1) as f32;
+        // println!(" >> {} -- {}", weather.hourly.time[i], pct);
+
+        let dt = hourly_times[i];
+
+        // Format as 12-hour with AM/PM
+        let formatted = dt.format("%-I %p").to_string(); // %-I = hour without leading zero
+        // let formatted = dt.format("%-I").to_string();
+
+        // println!("{formatted}"); // "12 AM"
+
+        if (index + 3) % 4 == 0 {
+            draw_text_blob_with_color(
+                canvas,
+                &mini_font,
+                (hourly_x_start as f32 + index as f32 * hourly_slot_width) as i32,
+                y + today_offset,
+                &formatted,
+                options.color,
+                0.5,
+            );
+        }
+    }
+
+    // temperature curve for today
+    let today_temp_range = get_temp_range(&temp_points);
+    draw_text_blob(
+        canvas,
+        &font_boss.emoji_font,
+        x + 10,
+        y + today_offset + 60,
+        "🌡️",
+    );
+    draw_hourly(
+        canvas,
+        &mini_font,
+        hourly_x_start,
+        y + today_offset + 10,
+        hourly_width,
+        hourly_height,
+        &temp_points,
+        "°",
+        today_temp_range,
+    );
+
+    draw_text_blob(
+        canvas,
+        &font_boss.emoji_font,
+        x + 10,
+        y + today_offset + hourly_height + 30 + 60,
+        "💧",
+    );
+    draw_hourly(
+        canvas,
+        &mini_font,
+        hourly_x_start,
+        y + today_offset + hourly_height + 40,
+        hourly_width,
+        hourly_height,
+        &precip_points,
+        "%",
+        (0.0, 100.0),
+    );
+
+    if let Some(daily) = &weather.daily {
+        // Seven columns at 102px; other counts share the same span
+        let day_width = 102.0 * 7.0 / options.daily_columns.max(1) as f32;
+
+        let num_daily_pts = daily.time.len().min(options.daily_columns);
+
+        let mut max_temp: f32 = -99999999.0;
+        let mut min_temp: f32 = 99999999.0;
+        for i in 0..num_daily_pts {
+            max_temp = max_temp.max(daily.temperature_max[i]);
+            min_temp = min_temp.min(daily.temperature_max[i]);
+            max_temp = max_temp.max(daily.temperature_min[i]);
+            min_temp = min_temp.min(daily.temperature_min[i]);
+        }
+        let temp_range = max_temp - min_temp;
+
+        let max_daily_vpixels_allowed = 105;
+        let pixels_per_degree = max_daily_vpixels_allowed as f32 / temp_range;
+
+        println!("min max  {} {}", max_temp, min_temp);
+        println!("max degree range {}", temp_range);
+        println!("max pixels {}", max_daily_vpixels_allowed);
+        println!("pixels per degree {}", pixels_per_degree);
+
+        for i in 0..num_daily_pts {
+            let date = daily_dates[i];
+
+            // Get the weekday (0 = Monday, 6 = Sunday if you want ISO, or 0 = Sunday with .num_days_from_sunday())
+            let weekday = date.weekday().num_days_from_sunday();
+
+            let px = x as f32 + 62.0 + i as f32 * day_width;
+            let x = px as i32 - 10;
+            let y = y as i32 + 350;
+
+            let qdiff = max_temp - daily.temperature_max[i] as f32;
+
+            let vpushdown = qdiff * pixels_per_degree;
+
+            println!(
+                "   {}: {}-{}, {} -> {}",
+                i, daily.temperature_min[i], daily.temperature_max[i], qdiff, vpushdown
+            );
+
+            let this_grad_off = vpushdown as i32;
+            let this_daily_height =
+                (pixels_per_degree * (daily.temperature_max[i] - daily.temperature_min[i])) as i32;
+
+            draw_text_blob_with_color(
+                canvas,
+                &mini_font,
+                x,
+                y + this_grad_off + 2,
+                &format!("{}°", daily.temperature_max[i].round()),
+                options.color,
+                0.5,
+            );
+
+            let grad_half_width = 10;
+            draw_temp_gradient(
+                canvas,
+                x - grad_half_width,
+                y + this_grad_off + 10,
+                grad_half_width * 2,
+                this_daily_height,
+            );
+
+            draw_text_blob_with_color(
+                canvas,
+                &mini_font,
+                x,
+                y + this_grad_off + this_daily_height + 28,
+                &format!("{}°", daily.temperature_min[i].round()),
+                options.color,
+                0.5,
+            );
+
+            let precip_text = format!("{}%", daily.precipitation_probability[i].round());
+
+            let label_margin = 7.0;
+            let precip_height = max_daily_vpixels_allowed + 30;
+            let day_label_width =
+                svg_width as f32 + mini_font.measure_str(&precip_text, None).0 + 5.0;
+            let half_width = day_label_width * 0.5;
+            let label_start = x as f32 - half_width;
+
+            draw_icon(
+                canvas,
+                &daily_icons[i],
+                label_start,
+                (y + precip_height) as f32 + 20.0,
+            );
+
+            draw_text_blob_with_color(
+                canvas,
+                &mini_font,
+                (label_start + label_margin) as i32 + svg_width as i32,
+                y + precip_height + 39,
+                &precip_text,
+                options.color,
+                0.0,
+            );
+
+            draw_text_blob_with_color(
+                canvas,
+                &font,
+                x,
+                y + precip_height + 70,
+                WEEKDAYS3[weekday as usize],
+                options.color,
+                0.5,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+pub struct LoadedSvg {
+    image: Image, // Final pre-rendered Skia image
+    pub width: f32,
+    pub height: f32,
+}
+
+pub fn svg_from_file(
+    path: &str,
+    target_width: u32,
+    target_height: u32,
+    scalar: f32,
+) -> Result<LoadedSvg, Box<dyn std::error::Error>> {
+    let svg_data = std::fs::read(path)?;
+
+    let options = usvg::Options::default();
+    let usvg_tree = usvg::Tree::from_data(&svg_data, &options)?;
+    let resvg_tree = ResvgTree::from_usvg(&usvg_tree);
+
+    // Render to full target size
+    let mut pixmap =
+        tiny_skia::Pixmap::new(target_width, target_height).ok_or("Failed to create pixmap")?;
+
+    let svg_size = resvg_tree.size;
+    let scale_x = target_width as f32 / svg_size.width();
+    let scale_y = target_height as f32 / svg_size.height();
+    let scale = scale_x.min(scale_y) * scalar; // Maintain aspect ratio
+
+    // Calculate translation to center the scaled image
+    let scaled_width = svg_size.width() * scale;
+    let scaled_height = svg_size.height() * scale;
+
+    let transform = tiny_skia::Transform::from_translate(0.0, 0.0).post_scale(scale, scale);
+
+    resvg_tree.render(transform, &mut pixmap.as_mut());
+
+    // Convert pixmap to skia_safe::Image
+    let image_info = ImageInfo::new(
+        (target_width as i32, target_height as i32),
+        ColorType::RGBA8888,
+        AlphaType::Premul,
+        None,
+    );
+
+    let image = Image::from_raster_data(
+        &image_info,
+        Data::new_copy(pixmap.data()),
+        (target_width * 4) as usize,
+    )
+    .ok_or("Failed to create Skia image")?;
+
+    Ok(LoadedSvg {
+        image,
+        width: scaled_width as f32,
+        height: scaled_height as f32,
+    })
+}
+
+fn process_verse_token(token: &str) -> (String, bool) {
+    // Check if token matches "[number]" using a simple pattern
+    if let Some(stripped) = token.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+        // Make sure the inside is numeric
+        if stripped.chars().all(|c| c.is_ascii_digit()) {
+            return (stripped.to_string(), true);
+        }
+    }
+
+    // Default: passthrough
+    (token.to_string(), false)
+}
+
+fn measure_and_draw(
+    canvas: &RenderCanvas,
+    font: &Font,
+    ref_font: &Font,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    tokens: &Vec<&str>,
+    attr: &str,
+    draw: bool,
+    ypad: i32,
+    text_color: Color,
+) -> f32 {
+    let spacew = font.measure_str(" ", None).0;
+    let padding = 25;
+
+    let raw_lh = line_height(&font) * 0.6;
+    let lh = raw_lh * 1.9;
+    let mut xp = 0.0;
+    let mut yp = raw_lh;
+
+    let target_width = (width - padding * 3) as f32;
+    let target_height = (height - padding * 3) as f32;
+
+    for token in tokens {
+        let (val, is_number) = process_verse_token(token);
+        let token = &val;
+
+        let use_font = if is_number { &ref_font } else { &font };
+        let push_up = if is_number { lh * 0.25 } else { 0.0 } as i32;
+        let color = if is_number {
+            Color::from_rgb(100, 100, 100)
+        } else {
+            text_color
+        };
+
+        let ww = use_font.measure_str(token, None).0;
+        // println!("{} - {}", token, ww);
+
+        if xp + ww > target_width {
+            xp = 0.0;
+            yp += lh;
+        }
+
+        if draw {
+            draw_text_blob_with_color(
+                canvas,
+                &use_font,
+                xp as i32 + x + padding,
+                yp as i32 + y + padding + ypad - push_up,
+                token,
+                color,
+                0.0,
+            );
+        }
+
+        xp += ww;
+        xp += spacew;
+    }
+
+    yp += lh;
+    xp = 0.0;
+
+    if draw {
+        draw_text_blob_with_color(
+            canvas,
+            &font,
+            xp as i32 + x + padding + target_width as i32,
+            yp as i32 + y + padding + ypad,
+            attr,
+            text_color,
+            1.0,
+        );
+    }
+
+    // draw at      (y + padding) + yp
+    // limit at     (y + padding) as f32 + target_height)
+
+    let leftover = target_height - yp;
+
+    // println!("yp {}  th {}", yp, target_height);
+
+    leftover
+}
+
+fn draw_verse(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    now: NaiveDateTime,
+    options: &VerseOptions,
+) {
+    let date_str = now.format("%B %d %Y %p").to_string();
+    // let date_str = now.format("%H:%M:%s").to_string();
+
+    // Hash the string
+    let mut hasher = DefaultHasher::new();
+    date_str.hash(&mut hasher);
+    let hash64 = hasher.finish() as u64;
+
+    println!("date_str = {date_str}");
+
+    // Example: deterministic selection
+    let db_path = "verses.db";
+
+    match get_verse_by_seed(db_path, hash64) {
+        Ok(Some((reference, text))) => {
+            println!("{} → {}", reference, text);
+            really_draw_verse(
+                canvas, font_boss, x, y, width, height, &reference, &text, options,
+            );
+        }
+        Ok(None) => println!("No verses found in DB."),
+        Err(e) => draw_feed_error(canvas, font_boss, x, y, width, "verse", &e.to_string()),
+    };
+}
+
+fn really_draw_verse(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    attr: &str,
+    verse: &str,
+    options: &VerseOptions,
+) {
+    let margin = 15;
+
+    draw_box_with_gradient(
+        canvas,
+        x + margin,
+        y + margin,
+        width - margin,
+        height - margin * 2,
+        Color::from_rgb(230, 230, 230),
+        Color::from_rgb(255, 255, 255),
+    );
+
+    let tokens: Vec<&str> = verse.split_whitespace().collect();
+
+    // Half-point steps down from the configured size to 5pt
+    let largest = (options.font.size * 2.0).round() as i32;
+    for i in (10..=largest.max(10)).rev() {
+        let fsize = i as f32 * 0.5;
+
+        let ref_font = font_boss.load_option(&FontOption {
+            face: options.font.face,
+            size: fsize * 0.8,
+        });
+        let font = font_boss.load_option(&FontOption {
+            face: options.font.face,
+            size: fsize,
+        });
+
+        let yleftover = measure_and_draw(
+            canvas,
+            &font,
+            &ref_font,
+            x + margin,
+            y + margin,
+            width,
+            height,
+            &tokens,
+            &attr,
+            false,
+            0,
+            options.color,
+        );
+
+        let fits = yleftover >= 0.0;
+
+        println!("{fsize} -> {fits}");
+
+        if fits {
+            // Now we can draw!
+            let ypad = (yleftover * 0.5).round();
+            let _ = measure_and_draw(
+                canvas,
+                &font,
+                &ref_font,
+                x + margin,
+                y + margin,
+                width,
+                height,
+                &tokens,
+                &attr,
+                true,
+                ypad as i32,
+                options.color,
+            );
+
+            break;
+        }
+    }
+}
+
+fn format_cents_commas(cents: i64) -> String {
+    let negative = if cents < 0 { true } else { false };
+    let cents = cents.abs();
+
+    let dollars = cents / 100;
+    let remainder = cents % 100;
+
+    let s = dollars.to_string();
+    let mut out = String::new();
+
+    // Insert commas from the right
+    let mut count = 0;
+    for ch in s.chars().rev() {
+        if count == 3 {
+            out.push(',');
+            count = 0;
+        }
+        out.push(ch);
+        count += 1;
+    }
+
+    let dollar_str: String = out.chars().rev().collect();
+    format!(
+        "{}${}.{:02}",
+        if negative { "−" } else { "" },
+        dollar_str,
+        remainder
+    )
+}
+
+fn today_multiplier(cleaning: &[DailyScore], today: NaiveDate) -> Option<i32> {
+    cleaning
+        .iter()
+        .find(|d| NaiveDate::parse_from_str(&d.date, "%Y-%m-%d").ok() == Some(today))
+        .map(|d| d.multiplier)
+}
+
+fn draw_people(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    x: i32,
+    y: i32,
+    width: i32,
+    data: &AllData,
+    options: &AllowanceOptions,
+) {
+    let font = font_boss.load_option(&options.font);
+    let mini_font = font_boss.load_font(20.0);
+    let mini_rb_font = font_boss.load_roboto_extra_bold_font(20.0);
+    let bold_font = font_boss.load_bold_font(25.0);
+
+    let (Ok(cleaning), Ok(balances), Ok(upcoming_payouts), Ok(names)) = (
+        &data.cleaning,
+        &data.balances,
+        &data.upcoming_payouts,
+        &data.names,
+    ) else {
+        return;
+    };
+    let cleaning = &cleaning.data;
+    let balances = &balances.data;
+    let upcoming_payouts = &upcoming_payouts.data;
+    let names = &names.data;
+
+    ///////////////////////////////////////////////////////////////////////
+    ///////////////////////////////////////////////////////////////////////
+
+    // ---- Build lookup tables ----
+    let balance_by_id: HashMap<u32, &PersonBalance> =
+        balances.iter().map(|b| (b.person_id, b)).collect();
+
+    let mut sums: HashMap<i64, PayoutSum> = HashMap::new();
+
+    for payout in upcoming_payouts {
+        let entry = sums.entry(payout.person_id).or_insert(PayoutSum {
+            positive: 0.0,
+            negative: 0.0,
+        });
+
+        if payout.payout_cents >= 0.0 {
+            entry.positive += payout.payout_cents;
+        } else {
+            entry.negative += payout.payout_cents;
+        }
+    }
+
+    let name_by_id: HashMap<u32, &str> = names
+        .iter()
+        .map(|n| (n.person_id, n.name.as_str()))
+        .collect();
+
+    // ---- Dates header ----
+    print!("Dates: ");
+    for day in cleaning {
+        let date = NaiveDate::parse_from_str(&day.date, "%Y-%m-%d").expect("invalid date");
+
+        let wd = match date.weekday() {
+            chrono::Weekday::Mon => "Mo",
+            chrono::Weekday::Tue => "Tu",
+            chrono::Weekday::Wed => "We",
+            chrono::Weekday::Thu => "Th",
+            chrono::Weekday::Fri => "Fr",
+            chrono::Weekday::Sat => "Sa",
+            chrono::Weekday::Sun => "Su",
+        };
+
+        print!("{}({}) ", wd, day.multiplier);
+    }
+    println!("\n");
+
+    // ---- Collect scores per person, in date order ----
+    let mut scores_by_person: HashMap<u32, Vec<&str>> = HashMap::new();
+
+    for day in cleaning {
+        for entry in &day.entries {
+            scores_by_person
+                .entry(entry.person_id)
+                .or_default()
+                .push(entry.score.as_str());
+        }
+    }
+
+    ///////////////////////////////////////////////////////////////////////
+    ///////////////////////////////////////////////////////////////////////
+
+    let mut xdraw = x + 22;
+
+    // See if we have a lucky multiplier today
+    if let Some(mult) = today_multiplier(&cleaning, data.now.date_naive())
+        && mult > 1
+    {
+        draw_filled_circle(
+            canvas,
+            Point::new((x + 21) as f32, (y + 8) as f32),
+            23.0,
+            Color::BLACK,
+        );
+
+        let big_bold_font = font_boss.load_roboto_extra_bold_font(35.0);
+        draw_text_blob_with_color(
+            canvas,
+            &big_bold_font,
+            x + 21,
+            y + 20,
+            &mult.to_string(),
+            Color::WHITE,
+            0.5,
+        );
+
+        xdraw += 55;
+    }
+
+    // If *anybody* got an X or a ? we put up the death circle
+    let yesterday = data.now.date_naive() - chrono::Duration::days(1);
+    let yesterday_str = yesterday.format("%Y-%m-%d").to_string();
+
+    let forgot_to_clean_yesterday = cleaning
+        .iter()
+        .find(|day| day.date == yesterday_str)
+        .map(|day| {
+            day.entries
+                .iter()
+                .any(|entry| entry.score == "❌" || entry.score == "❓")
+        })
+        .unwrap_or(false);
+
+    // ...and here it is
+    if forgot_to_clean_yesterday {
+        draw_filled_circle(
+            canvas,
+            Point::new((xdraw) as f32, (y + 8) as f32),
+            23.0,
+            Color::BLACK,
+        );
+
+        draw_text_blob_with_color(
+            canvas,
+            &font_boss.emoji_font,
+            xdraw,
+            y + 18,
+            "💀",
+            Color::WHITE,
+            0.5,
+        );
+    }
+
+    let num_dates = cleaning.len().min(options.cleaning_days);
+
+    // Score columns are 40px apart and end at the right edge, with the
+    // balances just left of them
+    let scores_x = x + width - 5 - options.cleaning_days as i32 * 40;
+    let balance_x = scores_x - 35;
+    let mut any_mults = false;
+
+    for j in 0..num_dates {
+        if cleaning[j].multiplier > 1 {
+            any_mults = true;
+            break;
+        }
+    }
+
+    // weekyday headers
+    for j in 0..num_dates {
+        let day = &cleaning[j];
+        let date = NaiveDate::parse_from_str(&day.date, "%Y-%m-%d").expect("invalid date");
+
+        let wd = match date.weekday() {
+            chrono::Weekday::Mon => "Mo",
+            chrono::Weekday::Tue => "Tu",
+            chrono::Weekday::Wed => "We",
+            chrono::Weekday::Thu => "Th",
+            chrono::Weekday::Fri => "Fr",
+            chrono::Weekday::Sat => "Sa",
+            chrono::Weekday::Sun => "Su",
+        };
+
+        let opt_mult: Option<i32> = if day.multiplier > 1 {
+            Some(day.multiplier)
+        } else {
+            None
+        }; //Some(2);
+
+        draw_text_blob_with_color(
+            canvas,
+            &mini_font,
+            scores_x + 19 + j as i32 * 40,
+            y + 18 - if any_mults { 0 } else { 6 },
+            wd,
+            options.color,
+            0.5,
+        );
+
+        if let Some(mult) = opt_mult {
+            draw_filled_circle(
+                canvas,
+                Point::new((scores_x + 19 + j as i32 * 40) as f32, (y - 9) as f32),
+                11.0,
+                Color::BLACK,
+            );
+
+            draw_text_blob_with_color(
+                canvas,
+                &mini_rb_font,
+                scores_x + 19 + j as i32 * 40,
+                y - 2,
+                &mult.to_string(),
+                Color::WHITE,
+                0.5,
+            );
+        }
+    }
+
+    let mut ordered_names = names.to_vec();
+    ordered_names.sort_by_key(|n| n.person_id);
+
+    // ---- Print rows ----
+    let mut i = 0;
+    for person in ordered_names {
+        let scores = scores_by_person.get(&person.person_id);
+        let name = name_by_id.get(&person.person_id).unwrap_or(&"Unknown");
+        let bal = balance_by_id.get(&person.person_id);
+        let upcoming = sums.get(&(person.person_id as i64));
+
+        let yoff = y + i as i32 * options.row_height + 60;
+
+        if let Some(b) = bal {
+            print!("{} -- {}, +{}, {}   ", name, b.balance_cents, 444, 444);
+        } else {
+            print!("{} -- (no balance)   ", name);
+        }
+
+        // name
+        draw_text_blob_with_color(canvas, &font, x, yoff, &name, options.color, 0.0);
+
+        if let Some(b) = bal {
+            // balance
+            draw_text_blob_with_color(
+                canvas,
+                &font,
+                balance_x,
+                yoff,
+                &format_cents_commas(b.balance_cents),
+                options.color,
+                1.0,
+            );
+
+            // weekly up/down
+            if let Some(up) = upcoming {
+                let downcents = (up.negative).round() as i64;
+                let upcents = (up.positive).round() as i64;
+
+                let down_balance = if downcents < 0 {
+                    format!("  {}", &format_cents_commas(downcents))
+                } else {
+                    "".to_string()
+                };
+
+                draw_text_blob_with_color(
+                    canvas,
+                    &mini_font,
+                    balance_x,
+                    yoff + 25,
+                    &format!("+{}{}", &format_cents_commas(upcents), &down_balance),
+                    options.color,
+                    1.0,
+                );
+            }
+        }
+
+        // cleaning emojis
+        if let Some(scores) = scores {
+            let today = data.now.date_naive();
+
+            for k in 0..num_dates.min(num_dates) {
+                let s = &scores[k];
+
+                // Replace ❓ with ❌ for past dates
+                let display_score = if *s == "❓" {
+                    let date = today - chrono::Duration::days((num_dates - 1 - k) as i64);
+                    if date < today { "❌" } else { s }
+                } else {
+                    s
+                };
+
+                print!("{} ", display_score);
+                draw_text_blob(
+                    canvas,
+                    &font_boss.emoji_font,
+                    scores_x + k as i32 * 40,
+                    yoff + 10,
+                    display_score,
+                );
+            }
+        }
+
+        println!();
+        i += 1;
+    }
+}
+
+/// Cross-checks the four allowance feeds, returning why they can't be drawn
+/// together. The widget only assumes what this has already checked.
+fn validate_allowance_data(
+    cleaning: &[DailyScore],
+    names: &[PersonName],
+    balances: &[PersonBalance],
+    upcoming_payouts: &[UpcomingPayout],
+    today: NaiveDate,
+) -> Result<(), String> {
+    if names.is_empty() {
+        return Err("names.json lists nobody".to_string());
+    }
+
+    let mut name_by_id: HashMap<u32, &str> = HashMap::new();
+    for person in names {
+        if name_by_id
+            .insert(person.person_id, person.name.as_str())
+            .is_some()
+        {
+            return Err(format!(
+                "Person {} is listed twice in names",
+                person.person_id
+            ));
+        }
+    }
+
+    for balance in balances {
+        if !name_by_id.contains_key(&balance.person_id) {
+            return Err(format!("Balance for unknown person {}", balance.person_id));
+        }
+    }
+
+    for payout in upcoming_payouts {
+        let known = u32::try_from(payout.person_id)
+            .is_ok_and(|person_id| name_by_id.contains_key(&person_id));
+        if !known {
+            return Err(format!("Payout for unknown person {}", payout.person_id));
+        }
+    }
+
+    let mut latest: Option<NaiveDate> = None;
+    let mut scores_by_person: BTreeMap<u32, usize> = BTreeMap::new();
+    for day in cleaning {
+        let date = NaiveDate::parse_from_str(&day.date, "%Y-%m-%d")
+            .map_err(|_| format!("Bad cleaning date '{}'", day.date))?;
+        if latest.is_some_and(|latest| date <= latest) {
+            return Err(format!("Cleaning dates out of order at {}", day.date));
+        }
+        latest = Some(date);
+
+        for entry in &day.entries {
+            if !name_by_id.contains_key(&entry.person_id) {
+                return Err(format!(
+                    "Cleaning score for unknown person {} on {}",
+                    entry.person_id, day.date
+                ));
+            }
+            *scores_by_person.entry(entry.person_id).or_default() += 1;
+        }
+    }
+
+    // Scores are drawn in columns by day, so everyone needs one per day
+    for (person_id, count) in &scores_by_person {
+        if *count != cleaning.len() {
+            return Err(format!(
+                "{} has {} cleaning scores for {} days",
+                name_by_id[person_id],
+                count,
+                cleaning.len()
+            ));
+        }
+    }
+
+    // The columns end at today, so older scores would land on the wrong days
+    if let Some(latest) = latest
+        && latest < today - chrono::Duration::days(1)
+    {
+        return Err(format!("Cleaning scores stop at {}", latest));
+    }
+
+    Ok(())
+}
+
+fn maybe_draw_people(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    x: i32,
+    y: i32,
+    width: i32,
+    _height: i32,
+    data: &AllData,
+    options: &AllowanceOptions,
+) {
+    let validated = match (
+        &data.cleaning,
+        &data.names,
+        &data.balances,
+        &data.upcoming_payouts,
+    ) {
+        (Ok(cleaning), Ok(names), Ok(balances), Ok(upcoming_payouts)) => validate_allowance_data(
+            &cleaning.data,
+            &names.data,
+            &balances.data,
+            &upcoming_payouts.data,
+            data.now.date_naive(),
+        ),
+        _ => Err("Allowance data is missing".to_string()),
+    };
+
+    match validated {
+        Ok(()) => draw_people(canvas, font_boss, x, y, width, data, options),
+        Err(reason) => {
+            println!("Not drawing allowances: {}", reason);
+            draw_feed_error(canvas, font_boss, x, y, width, "cleaning", &reason);
+        }
+    }
+}
+
+/// Shortens `text` with an ellipsis until it fits in `max_width` pixels.
+fn truncate_to_width(font: &Font, text: &str, max_width: f32) -> String {
+    if font.measure_str(text, None).0 <= max_width {
+        return text.to_string();
+    }
+
+    let mut chars: Vec<char> = text.chars().collect();
+    while !chars.is_empty() {
+        chars.pop();
+        let candidate = format!("{}…", chars.iter().collect::<String>().trim_end());
+        if font.measure_str(&candidate, None).0 <= max_width {
+            return candidate;
+        }
+    }
+
+    String::new()
+}
+
+fn draw_checkbox(canvas: &RenderCanvas, x: i32, y: i32, size: i32, checked: bool, color: Color) {
+    let mut paint = Paint::default();
+    paint.set_color(color);
+    paint.set_anti_alias(true);
+    paint.set_style(PaintStyle::Stroke);
+    paint.set_stroke_width(2.0);
+
+    let rect = Rect::from_xywh(x as f32, y as f32, size as f32, size as f32);
+    canvas.draw_rrect(RRect::new_rect_xy(rect, 3.0, 3.0), &paint);
+
+    if checked {
+        let s = size as f32;
+        let mut tick = Path::new();
+        tick.move_to(Point::new(x as f32 + s * 0.2, y as f32 + s * 0.5));
+        tick.line_to(Point::new(x as f32 + s * 0.42, y as f32 + s * 0.75));
+        tick.line_to(Point::new(x as f32 + s * 0.8, y as f32 + s * 0.25));
+        canvas.draw_path(&tick, &paint);
+    }
+}
+
+fn todo_due_label(due: NaiveDate, today: NaiveDate) -> String {
+    match days_between(due, today) {
+        d if d < 0 => "Overdue".to_string(),
+        0 => "Today".to_string(),
+        1 => "Tomorrow".to_string(),
+        d if d < 7 => WEEKDAYS3[due.weekday().num_days_from_sunday() as usize].to_string(),
+        _ => due.format("%b %-d").to_string(),
+    }
+}
+
+fn draw_todo(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    data: &AllData,
+    options: &TodoOptions,
+) {
+    let font = font_boss.load_option(&options.font);
+    let mini_font = font_boss.load_font(20.0);
+    let mini_bold_font = font_boss.load_bold_font(20.0);
+    let mini_italic_font = font_boss.load_italic_font(20.0);
+    let bold_font = font_boss.load_bold_font(25.0);
+    let gray = options.muted_color;
+
+    let row_height = options.row_height.max(1);
+    let box_size = 18;
+
+    let Ok(todo) = &data.todo else {
+        return;
+    };
+
+    // Names are only decoration here, so the list still draws without them
+    let name_by_id: HashMap<u32, &str> = match &data.names {
+        Ok(names) => names
+            .data
+            .iter()
+            .map(|n| (n.person_id, n.name.as_str()))
+            .collect(),
+        Err(_) => HashMap::new(),
+    };
+
+    // Open items first, soonest due first, then most important first
+    let mut items: Vec<(&TodoItem, Option<NaiveDate>)> = todo
+        .data
+        .iter()
+        .map(|item| {
+            let due = item
+                .due
+                .as_deref()
+                .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok());
+            (item, due)
+        })
+        .collect();
+    items.sort_by_key(|(item, due)| {
+        (
+            item.done,
+            due.is_none(),
+            *due,
+            std::cmp::Reverse(item.priority),
+        )
+    });
+
+    // Whatever doesn't fit collapses into a "+N more" row
+    let max_rows = (height / row_height).max(1) as usize;
+    let shown = if items.len() > max_rows {
+        max_rows - 1
+    } else {
+        items.len()
+    };
+    let hidden = items.len() - shown;
+
+    let mut yoff = y + 20;
+    for (item, due) in items.iter().take(shown) {
+        let color = if item.done || item.priority == TodoPriority::Low {
+            gray
+        } else {
+            options.color
+        };
+
+        draw_checkbox(canvas, x, yoff - box_size + 2, box_size, item.done, color);
+
+        // Right-hand side: due date, then who it's assigned to
+        let mut right_x = x + width - 25;
+
+        if let Some(due) = due {
+            let overdue = !item.done && days_between(*due, data.now.date_naive()) < 0;
+            let label = todo_due_label(*due, data.now.date_naive());
+            let font = if overdue { &mini_bold_font } else { &mini_font };
+            draw_text_blob_with_color(canvas, font, right_x, yoff, &label, color, 1.0);
+            right_x -= font.measure_str(&label, None).0 as i32 + 15;
+        }
+
+        if let Some(name) = item.person_id.and_then(|id| name_by_id.get(&id)) {
+            draw_text_blob_with_color(canvas, &mini_italic_font, right_x, yoff, name, gray, 1.0);
+            right_x -= mini_italic_font.measure_str(name, None).0 as i32 + 15;
+        }
+
+        // Left-hand side: priority marker and title
+        let mut text_x = x + box_size + 12;
+
+        if item.priority == TodoPriority::High && !item.done {
+            draw_text_blob_with_color(canvas, &bold_font, text_x, yoff, "!!", color, 0.0);
+            text_x += bold_font.measure_str("!!", None).0 as i32 + 6;
+        }
+
+        let title = truncate_to_width(&font, &item.title, (right_x - text_x) as f32);
+        draw_text_blob_with_color(canvas, &font, text_x, yoff, &title, color, 0.0);
+
+        if item.done {
+            let title_w = font.measure_str(&title, None).0;
+            let strike_y = (yoff - 7) as f32;
+            draw_colored_line(
+                canvas,
+                Point::new(text_x as f32, strike_y),
+                Point::new(text_x as f32 + title_w, strike_y),
+                gray,
+            );
+        }
+
+        yoff += row_height;
+    }
+
+    if hidden > 0 {
+        draw_text_blob_with_color(
+            canvas,
+            &mini_italic_font,
+            x + box_size + 12,
+            yoff,
+            &format!("+{} more", hidden),
+            gray,
+            0.0,
+        );
+    }
+}
+
+fn draw_date(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    x: i32,
+    y: i32,
+    width: i32,
+    _height: i32,
+    now: NaiveDateTime,
+    options: &DateOptions,
+) {
+    let font = font_boss.load_option(&options.font);
+    let bold_font = font_boss.load_bold_font(options.font.size);
+
+    // Extract the parts
+    let wday_text = now.format("%A").to_string();
+    let date_text = now.format("%B %d").to_string();
+    let year_text = now.format("%Y").to_string();
+
+    let wday = font.measure_str(&wday_text, None).0;
+    let date = bold_font.measure_str(&date_text, None).0;
+    let year = font.measure_str(&year_text, None).0;
+    let space = font.measure_str(" ", None).0 * 1.5;
+
+    let lh = line_height(&font);
+
+    draw_text_blob_with_color(
+        canvas,
+        &font,
+        x + width - 10 - (wday + date + year + space * 3.0) as i32,
+        (y as f32 + lh) as i32 - 2,
+        &wday_text,
+        options.color,
+        0.0,
+    );
+
+    draw_text_blob_with_color(
+        canvas,
+        &bold_font,
+        x + width - 10 - (date + year + space * 2.0) as i32,
+        (y as f32 + lh) as i32 - 2,
+        &date_text,
+        options.color,
+        0.0,
+    );
+
+    draw_text_blob_with_color(
+        canvas,
+        &font,
+        x + width - 10 - (year + space * 1.0) as i32,
+        (y as f32 + lh) as i32 - 2,
+        &year_text,
+        options.color,
+        0.0,
+    );
+}
+
+fn handle_child(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    node: &LayoutNode,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    data: &AllData,
+) {
+    let [border, content] = node.boxes(x, y, width, height);
+
+    if let Some(method) = node.dither() {
+        canvas.mark_dither(border.0, border.1, border.2, border.3, method);
+    }
+    if !node.is_container() {
+        canvas.outlines.borrow_mut().push(border);
+    }
+
+    // Widgets only ever see their content box
+    let (x, y, width, height) = content;
+
+    if let Some((feed, reason)) = data.first_error(node.feeds()) {
+        draw_feed_error(canvas, font_boss, x, y, width, feed, reason);
+        return;
+    }
+
+    let feed_status = data.worst_status(node.feeds());
+    if let Some(status) = feed_status
+        && status.staleness == Staleness::TooOld
+    {
+        draw_too_old_placeholder(canvas, font_boss, x, y, status.age_hours);
+        return;
+    }
+
+    draw_node(canvas, font_boss, node, x, y, width, height, data);
+
+    if let Some(status) = feed_status
+        && status.staleness == Staleness::Stale
+    {
+        draw_staleness_badge(canvas, font_boss, x, y, width, status.age_hours);
+    }
+}
+
+fn draw_node(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    node: &LayoutNode,
+    x: i32,
+    y: i32,
+    width: i32,
+    height: i32,
+    data: &AllData,
+) {
+    match node {
+        LayoutNode::Container(container) => {
+            handle_container(
+                canvas,
+                font_boss,
+                container,
+                &container.split,
+                x,
+                y,
+                width,
+                height,
+                data,
+            );
+        }
+        LayoutNode::Grid(grid) => {
+            handle_grid(canvas, font_boss, grid, x, y, width, height, data);
+        }
+        LayoutNode::Overlay(overlay) => {
+            handle_overlay(canvas, font_boss, overlay, x, y, width, height, data);
+        }
+        LayoutNode::Date(node) => {
+            draw_date(
+                canvas,
+                font_boss,
+                x,
+                y,
+                width,
+                height,
+                data.local_now(node.options.zone),
+                &node.options,
+            );
+
+            // draw_rect_thing(canvas, x, y, width, height);
+
+            // date: render a filled rounded rect and big text
+            // let mut paint = Paint::default();
+            // paint.set_color(Color::from_rgb(240, 240, 240));
+            // paint.set_anti_alias(true);
+            // paint.set_style(PaintStyle::Fill);
+
+            // let rect = Rect::from_xywh(x as f32 + 4.0, y as f32 + 4.0, width as f32 - 8.0, height as f32 - 8.0);
+            // let rrect = RRect::new_rect_xy(rect, 8.0, 8.0);
+            // canvas.draw_rrect(rrect, &paint);
+        }
+        LayoutNode::Todo(node) => {
+            // draw_rect_thing(canvas, x, y, width, height);
+            draw_todo(canvas, font_boss, x, y, width, height, data, &node.options);
+        }
+        LayoutNode::Weather(node) => {
+            if let Ok(weather) = &data.weather {
+                draw_weather_wrapped(
+                    canvas,
+                    &font_boss,
+                    x,
+                    y,
+                    width,
+                    height,
+                    &weather.data,
+                    // Open-Meteo's times are wall-clock times where the forecast is
+                    data.local_now(ZoneSource::Weather),
+                    &node.options,
+                );
+            }
+        }
+        LayoutNode::HLine(node) => {
+            // draw_rect_thing(canvas, x, y, width, height);
+            let hbuf = 0.0;
+            let loc = (y as f32 + (y + height) as f32) * 0.5;
+            let start = Point::new(x as f32 + hbuf, loc); // Start coordinates
+            let end = Point::new((x + width) as f32 - hbuf, loc); // End coordinates
+            draw_colored_line(canvas, start, end, node.options.color);
+        }
+        LayoutNode::VLine(node) => {
+            // draw_rect_thing(canvas, x, y, width, height);
+            let vbuf = 0.0;
+            let loc = (x as f32 + (x + width) as f32) * 0.5;
+            let start = Point::new(loc, y as f32 + vbuf); // Start coordinates
+            let end = Point::new(loc, (y + height) as f32 - vbuf); // End coordinates
+            draw_colored_line(canvas, start, end, node.options.color);
+        }
+        LayoutNode::Allowance(node) => {
+            maybe_draw_people(canvas, font_boss, x, y, width, height, data, &node.options);
+        }
+        LayoutNode::Countdown(node) => {
+            let options = &node.options;
+            let font = font_boss.load_option(&options.font);
+
+            let Ok(sig_dates) = &data.significant_dates else {
+                return;
+            };
+            let targets = match countdown_targets(sig_dates) {
+                Ok(targets) => targets,
+                Err(reason) => {
+                    draw_feed_error(canvas, font_boss, x, y, width, "dates", &reason);
+                    return;
+                }
+            };
+            let today = data.local_now(options.zone).date();
+
+            let mut yoff = y + 20;
+            for i in 0..sig_dates.len() {
+                let diff = days_between(targets[i], today);
+
+                if diff >= 0 {
+                    // draw_rect_thing(canvas, x, y, width, height);
+                    draw_text_blob(
+                        canvas,
+                        &font_boss.emoji_font,
+                        x,
+                        yoff - 2,
+                        &sig_dates[i].emoji,
+                    );
+                    draw_text_blob_with_color(
+                        canvas,
+                        &font,
+                        x + 45,
+                        yoff,
+                        &sig_dates[i].name,
+                        options.color,
+                        0.0,
+                    );
+                    draw_text_blob_with_color(
+                        canvas,
+                        &font,
+                        x + width - 25,
+                        yoff,
+                        &format!("{}", diff),
+                        options.color,
+                        1.0,
+                    );
+
+                    yoff += options.row_spacing;
+                }
+            }
+        }
+        LayoutNode::Battery(node) => {
+            let battery = &node.options;
+            if battery.show_updated {
+                let mini_font = font_boss.load_italic_font(20.0);
+                draw_text_blob_with_color(
+                    canvas,
+                    &mini_font,
+                    x - 5,
+                    y,
+                    &last_updated_string(data.now.naive_local()),
+                    Color::from_rgb(128, 128, 128),
+                    0.0,
+                );
+            }
+
+            draw_battery(
+                canvas,
+                font_boss,
+                x,
+                y,
+                width,
+                height,
+                data.battery().map(|battery| &battery.data),
+                battery.low_percent,
+            );
+        }
+        LayoutNode::Verse(node) => {
+            draw_verse(
+                canvas,
+                font_boss,
+                x,
+                y,
+                width,
+                height,
+                data.now.naive_local(),
+                &node.options,
+            );
+        }
+    }
+}
+
+fn draw_problem(canvas: &RenderCanvas, font_boss: &FontBoss, x: i32, y: i32, message: &str) {
+    draw_text_blob(canvas, &font_boss.emoji_font, x + 20, y + 40, "😞");
+    draw_text_blob(canvas, &font_boss.main_font, x + 70, y + 40, message);
+}
+
+/// What a feed is called in its widget's error placeholder.
+fn feed_label(feed: &str) -> &str {
+    match feed {
+        "cleaning" | "names" | "balances" | "upcoming_payouts" => "allowances",
+        "todo" => "to-dos",
+        other => other,
+    }
+}
+
+/// Stands in for a widget whose feed couldn't be loaded, with the reason
+/// underneath in small print.
+fn draw_feed_error(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    x: i32,
+    y: i32,
+    width: i32,
+    feed: &str,
+    reason: &str,
+) {
+    draw_problem(
+        canvas,
+        font_boss,
+        x,
+        y,
+        &format!("Problem getting {}", feed_label(feed)),
+    );
+
+    let mini_font = font_boss.load_italic_font(20.0);
+    let reason = truncate_to_width(&mini_font, reason, (width - 90).max(0) as f32);
+    draw_text_blob_with_color(
+        canvas,
+        &mini_font,
+        x + 70,
+        y + 68,
+        &reason,
+        Color::from_rgb(96, 96, 96),
+        0.0,
+    );
+}
+
+/// Stands in for a widget whose data is past its hide_after_hours.
+fn draw_too_old_placeholder(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    x: i32,
+    y: i32,
+    age_hours: f64,
+) {
+    draw_text_blob(canvas, &font_boss.emoji_font, x + 20, y + 40, "😴");
+
+    draw_text_blob(
+        canvas,
+        &font_boss.main_font,
+        x + 70,
+        y + 40,
+        &format!("No data for {}", format_age(age_hours)),
+    );
+}
+
+/// Small outlined "5h old" tag in the widget's top-right corner.
+fn draw_staleness_badge(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    x: i32,
+    y: i32,
+    width: i32,
+    age_hours: f64,
+) {
+    let font = font_boss.load_italic_font(18.0);
+    let text = format!("{} old", format_age(age_hours));
+    let (advance, _) = font.measure_str(&text, None);
+
+    let pad = 6.0;
+    let badge_w = advance + pad * 2.0;
+    let badge_h = 26.0;
+    let left = (x + width) as f32 - badge_w - 4.0;
+    let top = y as f32 + 4.0;
+
+    let rect = Rect::from_xywh(left, top, badge_w, badge_h);
+    let mut fill = Paint::default();
+    fill.set_color(Color::WHITE);
+    fill.set_style(PaintStyle::Fill);
+    canvas.draw_rrect(RRect::new_rect_xy(rect, 6.0, 6.0), &fill);
+
+    let mut outline = Paint::default();
+    outline.set_color(Color::from_rgb(96, 96, 96));
+    outline.set_anti_alias(true);
+    outline.set_style(PaintStyle::Stroke);
+    outline.set_stroke_width(1.5);
+    canvas.draw_rrect(RRect::new_rect_xy(rect, 6.0, 6.0), &outline);
+    canvas.mark_text(rect);
+
+    draw_text_blob_with_color(
+        canvas,
+        &font,
+        (left + pad) as i32,
+        (top + 19.0) as i32,
+        &text,
+        Color::from_rgb(64, 64, 64),
+        0.0,
+    );
+}
+
+fn draw_battery_glyph(canvas: &RenderCanvas, x: i32, y: i32, percent: f64, charging: bool) {
+    let body_w = 32.0;
+    let body_h = 14.0;
+    let (x, y) = (x as f32, y as f32);
+
+    let mut stroke = Paint::default();
+    stroke.set_color(Color::BLACK);
+    stroke.set_anti_alias(true);
+    stroke.set_style(PaintStyle::Stroke);
+    stroke.set_stroke_width(2.0);
+
+    let mut fill = Paint::default();
+    fill.set_color(Color::BLACK);
+    fill.set_anti_alias(true);
+    fill.set_style(PaintStyle::Fill);
+
+    // Body and the little nub on the positive end
+    let body = Rect::from_xywh(x, y, body_w, body_h);
+    canvas.draw_rrect(RRect::new_rect_xy(body, 2.0, 2.0), &stroke);
+    canvas.draw_rect(
+        Rect::from_xywh(x + body_w, y + 4.0, 3.0, body_h - 8.0),
+        &fill,
+    );
+
+    // Charge level
+    let inset = 3.0;
+    let level = (percent.clamp(0.0, 100.0) / 100.0) as f32;
+    let level_w = (body_w - inset * 2.0) * level;
+    if level_w > 0.0 {
+        canvas.draw_rect(
+            Rect::from_xywh(x + inset, y + inset, level_w, body_h - inset * 2.0),
+            &fill,
+        );
+    }
+
+    if charging {
+        // Lightning bolt, knocked out of whatever is behind it
+        let cx = x + body_w * 0.5;
+        let mut bolt = Path::new();
+        bolt.move_to(Point::new(cx + 2.0, y - 2.0));
+        bolt.line_to(Point::new(cx - 5.0, y + body_h * 0.55));
+        bolt.line_to(Point::new(cx, y + body_h * 0.55));
+        bolt.line_to(Point::new(cx - 2.0, y + body_h + 2.0));
+        bolt.line_to(Point::new(cx + 5.0, y + body_h * 0.45));
+        bolt.line_to(Point::new(cx, y + body_h * 0.45));
+        bolt.close();
+
+        let mut halo = Paint::default();
+        halo.set_color(Color::WHITE);
+        halo.set_anti_alias(true);
+        halo.set_style(PaintStyle::Stroke);
+        halo.set_stroke_width(3.0);
+        canvas.draw_path(&bolt, &halo);
+        canvas.draw_path(&bolt, &fill);
+    }
+}
+
+fn draw_battery(
+    canvas: &RenderCanvas,
+    font_boss: &FontBoss,
+    x: i32,
+    y: i32,
+    width: i32,
+    _height: i32,
+    battery: Option<&BatteryReport>,
+    low_percent: f64,
+) {
+    let mini_font = font_boss.load_font(20.0);
+    let mini_bold_font = font_boss.load_bold_font(20.0);
+    let gray = Color::from_rgb(128, 128, 128);
+
+    let Some(battery) = battery else {
+        draw_text_blob_with_color(
+            canvas,
+            &font_boss.load_italic_font(20.0),
+            x + width - 25,
+            y,
+            "No battery report",
+            gray,
+            1.0,
+        );
+        return;
+    };
+
+    let low = battery.percent <= low_percent && !battery.charging;
+
+    // Glyph sits at the right edge, text is right-aligned against it
+    let glyph_x = x + width - 25 - 35;
+    draw_battery_glyph(canvas, glyph_x, y - 14, battery.percent, battery.charging);
+
+    let pct_text = format!("{}%", battery.percent.round());
+    let pct_font = if low { &mini_bold_font } else { &mini_font };
+    let pct_x = glyph_x - 8;
+    draw_text_blob_with_color(
+        canvas,
+        pct_font,
+        pct_x,
+        y,
+        &pct_text,
+        if low { Color::BLACK } else { gray },
+        1.0,
+    );
+
+    if low {
+        let pct_w = pct_font.measure_str(&pct_text, None).0 as i32;
+        draw_text_blob_with_color(
+            canvas,
+            &mini_bold_font,
+            pct_x - pct_w - 10,
+            y,
+            "Low battery",
+            Color::BLACK,
+            1.0,
+        );
+    }
+}
+
+fn last_updated_string(now: NaiveDateTime) -> String {
+    now.format("Updated %a %b %-d, %-I:%M %p").to_string()
+}
+
+/// Read the inner payload of an envelope file and return (payload, age)
+pub fn read_envelope<T: DeserializeOwned>(
+    path: &str,
+    now: DateTime<Utc>,
+) -> io::Result<(T, FeedAge)> {
+    let content = fs::read_to_string(path)?;
+    let envelope: StateEnvelope =
+        serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    let last_good = envelope
+        .last_good
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "No last_good data in envelope"))?;
+
+    let payload: T = serde_json::from_value(last_good.data)
+        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    let age = now.signed_duration_since(last_good.fetched_at);
+    let hours_old = age.num_seconds() as f64 / 3600.0;
+
+    Ok((
+        payload,
+        FeedAge {
+            age_hours: hours_old,
+            expired: last_good.expires.is_some_and(|expires| now > expires),
+        },
+    ))
+}
+
+/// ---- Output encoders: quantized levels -> device byte layout ----
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+enum OutputFormat {
+    /// 8 grays, 3 bits in the high end of each nibble, high nibble first
+    Gray3,
+    /// 1bpp black/white, MSB first, 1 = white
+    Mono,
+    /// 2bpp 4-gray, first pixel in the top bits
+    Gray2,
+    /// 4bpp 16-gray, high nibble first
+    Gray4,
+    /// Black/white/red: a 1bpp black/white plane, then a 1bpp red plane
+    Bwr,
+}
+
+impl OutputFormat {
+    fn encoder(self) -> Box<dyn FrameEncoder> {
+        match self {
+            OutputFormat::Gray3 => Box::new(Gray3Encoder),
+            OutputFormat::Mono => Box::new(PackedGrayEncoder { bits: 1 }),
+            OutputFormat::Gray2 => Box::new(PackedGrayEncoder { bits: 2 }),
+            OutputFormat::Gray4 => Box::new(PackedGrayEncoder { bits: 4 }),
+            OutputFormat::Bwr => Box::new(BwrEncoder),
+        }
+    }
+}
+
+/// How packed pixels are laid out in the output buffer.
+#[derive(Debug, Clone, Copy)]
+enum ByteLayout {
+    /// One `bits`-wide field per pixel, first pixel in the high bits,
+    /// each row padded out to a whole byte.
+    Packed { bits: usize },
+    /// A full 1bpp black/white plane followed by a full 1bpp red plane.
+    TwoPlane,
+}
+
+impl ByteLayout {
+    fn bits(self) -> usize {
+        match self {
+            ByteLayout::Packed { bits } => bits,
+            ByteLayout::TwoPlane => 1,
+        }
+    }
+
+    fn planes(self) -> usize {
+        match self {
+            ByteLayout::Packed { .. } => 1,
+            ByteLayout::TwoPlane => 2,
+        }
+    }
+
+    fn row_bytes(self, width: usize) -> usize {
+        (width * self.bits()).div_ceil(8)
+    }
+
+    fn packed_len(self, width: usize, height: usize) -> usize {
+        self.row_bytes(width) * height * self.planes()
+    }
+
+    /// Byte offsets within a packed row that hold pixels `x..x + width`.
+    fn byte_span(self, x: usize, width: usize) -> std::ops::Range<usize> {
+        x * self.bits() / 8..((x + width) * self.bits()).div_ceil(8)
+    }
+}
+
+/// A device frame format. The encoder says how many levels to dither to and
+/// how they end up in bytes, so the dither and pack stages always agree.
+trait FrameEncoder {
+    /// Gray levels to quantize to; level 0 is black, `levels() - 1` is white.
+    fn levels(&self) -> usize;
+
+    /// Whether red pixels are carried through as their own level, `levels()`.
+    fn has_red(&self) -> bool {
+        false
+    }
+
+    fn layout(&self) -> ByteLayout;
+
+    fn packed_len(&self, width: usize, height: usize) -> usize {
+        self.layout().packed_len(width, height)
+    }
+
+    /// Packs quantized level indices into the device byte layout.
+    fn pack(&self, idx: &[u8], width: usize, height: usize) -> Vec<u8>;
+
+    /// The inverse of `pack`; `packed` must be `packed_len` bytes.
+    fn unpack(&self, packed: &[u8], width: usize, height: usize) -> Vec<u8>;
+}
+
+struct Gray3Encoder;
+
+impl FrameEncoder for Gray3Encoder {
+    fn levels(&self) -> usize {
+        8
+    }
+
+    fn layout(&self) -> ByteLayout {
+        ByteLayout::Packed { bits: 4 }
+    }
+
+    fn pack(&self, idx: &[u8], width: usize, height: usize) -> Vec<u8> {
+        pack_3bpp_high_first(idx, width, height)
+    }
+
+    fn unpack(&self, packed: &[u8], width: usize, height: usize) -> Vec<u8> {
+        unpack_3bpp_high_first(packed, width, height)
+    }
+}
+
+/// Plain 1, 2 or 4 bit gray, one level per field.
+struct PackedGrayEncoder {
+    bits: usize,
+}
+
+impl FrameEncoder for PackedGrayEncoder {
+    fn levels(&self) -> usize {
+        1 << self.bits
+    }
+
+    fn layout(&self) -> ByteLayout {
+        ByteLayout::Packed { bits: self.bits }
+    }
+
+    fn pack(&self, idx: &[u8], width: usize, height: usize) -> Vec<u8> {
+        pack_bits_high_first(idx, width, height, self.bits)
+    }
+
+    fn unpack(&self, packed: &[u8], width: usize, height: usize) -> Vec<u8> {
+        unpack_bits_high_first(packed, width, height, self.bits)
+    }
+}
+
+/// Waveshare / GoodDisplay style three-color panels.
+struct BwrEncoder;
+
+impl FrameEncoder for BwrEncoder {
+    fn levels(&self) -> usize {
+        2
+    }
+
+    fn has_red(&self) -> bool {
+        true
+    }
+
+    fn layout(&self) -> ByteLayout {
+        ByteLayout::TwoPlane
+    }
+
+    fn pack(&self, idx: &[u8], width: usize, height: usize) -> Vec<u8> {
+        // Plane 1: 1 = white. Plane 2: 1 = red. Red pixels read as white in
+        // the black/white plane so the controller only inks them once.
+        let bw: Vec<u8> = idx.iter().map(|&v| (v != 0) as u8).collect();
+        let red: Vec<u8> = idx.iter().map(|&v| (v == 2) as u8).collect();
+
+        let mut out = pack_bits_high_first(&bw, width, height, 1);
+        out.extend(pack_bits_high_first(&red, width, height, 1));
+        out
+    }
+
+    fn unpack(&self, packed: &[u8], width: usize, height: usize) -> Vec<u8> {
+        let (bw, red) = packed.split_at(packed.len() / 2);
+        let bw = unpack_bits_high_first(bw, width, height, 1);
+        let red = unpack_bits_high_first(red, width, height, 1);
+
+        bw.iter()
+            .zip(&red)
+            .map(|(&v, &r)| if r == 1 { 2 } else { v })
+            .collect()
+    }
+}
+
+/// Marks pixels that are clearly red, for panels with a red ink.
+fn red_mask(rgba: &[u8]) -> Vec<u8> {
+    rgba.chunks_exact(4)
+        .map(|px| (px[0] >= 160 && px[1] < 100 && px[2] < 100) as u8)
+        .collect()
+}
+
+/// Dithers to the encoder's level indices, ready for `FrameEncoder::pack`.
+fn dither_levels(
+    image_data: &[u8],
+    methods: &[DitherMethod],
+    red: Option<&[u8]>,
+    width: usize,
+    height: usize,
+    encoder: &dyn FrameEncoder,
+) -> Vec<u8> {
+    // Step 1: Dither to the encoder's levels, region by region
+    let mut dithered = quantize(image_data, methods, width, height, encoder.levels());
+
+    // Step 1b: Red pixels bypass the dither entirely
+    if encoder.has_red()
+        && let Some(red) = red
+    {
+        let red_level = encoder.levels() as u8;
+        for (level, &is_red) in dithered.iter_mut().zip(red) {
+            if is_red != 0 {
+                *level = red_level;
+            }
+        }
+    }
+
+    dithered
+}
+
+/// ---- Dithering: gray values -> level indices ----
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum DitherMethod {
+    /// No dithering, just snap to the nearest level
+    None,
+    FloydSteinberg,
+    Atkinson,
+    Stucki,
+    Sierra,
+    /// Ordered dithering with an 8x8 Bayer matrix
+    Bayer,
+    /// Ordered dithering with a 64x64 void-and-cluster blue-noise mask
+    BlueNoise,
+}
+
+impl DitherMethod {
+    fn ditherer(self) -> Box<dyn Ditherer> {
+        match self {
+            DitherMethod::None => Box::new(NearestLevel),
+            DitherMethod::FloydSteinberg => Box::new(ErrorDiffusion {
+                kernel: &FLOYD_STEINBERG,
+                norm: 16.0,
+            }),
+            DitherMethod::Atkinson => Box::new(ErrorDiffusion {
+                kernel: &ATKINSON,
+                norm: 8.0,
+            }),
+            DitherMethod::Stucki => Box::new(ErrorDiffusion {
+                kernel: &STUCKI,
+                norm: 42.0,
+            }),
+            DitherMethod::Sierra => Box::new(ErrorDiffusion {
+                kernel: &SIERRA,
+                norm: 32.0,
+            }),
+            DitherMethod::Bayer => Box::new(Ordered { map: bayer_map() }),
+            DitherMethod::BlueNoise => Box::new(Ordered {
+                map: blue_noise_map(),
+            }),
+        }
+    }
+}
+
+trait Ditherer {
+    /// Quantizes 0-255 gray values to level indices `0..levels`.
+    fn dither(&self, gray: &[u8], width: usize, height: usize, levels: usize) -> Vec<u8>;
+}
+
+/// Dithers each region with its own method. Every method in use runs over
+/// the whole frame so error diffusion doesn't see artificial edges.
+fn quantize(
+    gray: &[u8],
+    methods: &[DitherMethod],
+    width: usize,
+    height: usize,
+    levels: usize,
+) -> Vec<u8> {
+    let mut used = methods.to_vec();
+    used.sort();
+    used.dedup();
+
+    if let [only] = used[..] {
+        return only.ditherer().dither(gray, width, height, levels);
+    }
+
+    let mut out = vec![0u8; gray.len()];
+    for method in used {
+        let dithered = method.ditherer().dither(gray, width, height, levels);
+        for (i, _) in methods.iter().enumerate().filter(|(_, m)| **m == method) {
+            out[i] = dithered[i];
+        }
+    }
+
+    out
+}
+
+struct NearestLevel;
+
+impl Ditherer for NearestLevel {
+    fn dither(&self, gray: &[u8], _width: usize, _height: usize, levels: usize) -> Vec<u8> {
+        assert!(levels >= 2, "levels must be >= 2");
+        let step = 255.0 / (levels - 1) as f32;
+
+        gray.iter()
+            .map(|&val| ((val as f32 / step).round() as i32).clamp(0, (levels - 1) as i32) as u8)
+            .collect()
+    }
+}
+
+/// (dx, dy, weight) taps, relative to the current pixel on a left-to-right row.
+type DiffusionKernel = [(isize, isize, u8)];
+
+// right, bottom-left, bottom, bottom-right with weights 7/16, 3/16, 5/16, 1/16
+const FLOYD_STEINBERG: [(isize, isize, u8); 4] = [(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)];
+
+// Only 6/8 of the error is passed on, which keeps highlights and shadows clean
+const ATKINSON: [(isize, isize, u8); 6] = [
+    (1, 0, 1),
+    (2, 0, 1),
+    (-1, 1, 1),
+    (0, 1, 1),
+    (1, 1, 1),
+    (0, 2, 1),
+];
+
+const STUCKI: [(isize, isize, u8); 12] = [
+    (1, 0, 8),
+    (2, 0, 4),
+    (-2, 1, 2),
+    (-1, 1, 4),
+    (0, 1, 8),
+    (1, 1, 4),
+    (2, 1, 2),
+    (-2, 2, 1),
+    (-1, 2, 2),
+    (0, 2, 4),
+    (1, 2, 2),
+    (2, 2, 1),
+];
+
+const SIERRA: [(isize, isize, u8); 10] = [
+    (1, 0, 5),
+    (2, 0, 3),
+    (-2, 1, 2),
+    (-1, 1, 4),
+    (0, 1, 5),
+    (1, 1, 4),
+    (2, 1, 2),
+    (-1, 2, 2),
+    (0, 2, 3),
+    (1, 2, 2),
+];
+
+struct ErrorDiffusion {
+    kernel: &'static DiffusionKernel,
+    norm: f32,
+}
+
+impl Ditherer for ErrorDiffusion {
+    fn dither(&self, gray: &[u8], width: usize, height: usize, levels: usize) -> Vec<u8> {
+        assert!(levels >= 2, "levels must be >= 2");
+
+        let mut work = vec![0.0f32; gray.len()];
+        for (i, &val) in gray.iter().enumerate() {
+            work[i] = val as f32;
+        }
+
+        let mut out = vec![0u8; gray.len()];
+        let step = 255.0 / (levels - 1) as f32;
+
+        for y in 0..height {
+            // Serpentine scan (zigzag left-right)
+            let (x_start, x_end, x_step) = if y % 2 == 1 {
+                (width - 1, usize::MAX, -1isize)
+            } else {
+                (0, width, 1isize)
+            };
+
+            let mut x = x_start;
+            while x != x_end {
+                let i = y * width + x;
+                let val = work[i];
+
+                // Quantize to nearest level
+                let k = (val / step).round() as i32;
+                let k = k.clamp(0, (levels - 1) as i32) as u8;
+                out[i] = k;
+
+                // Calculate quantization error
+                let qv = k as f32 * step;
+                let err = val - qv;
+
+                // Diffuse error to neighbors
+                for &(dx, dy, w) in self.kernel {
+                    let dx_adj = if y % 2 == 1 { -dx } else { dx };
+                    let nx = x as isize + dx_adj;
+                    let ny = y as isize + dy;
+
+                    if nx >= 0 && nx < width as isize && ny >= 0 && ny < height as isize {
+                        let j = (ny as usize) * width + (nx as usize);
+                        work[j] = (work[j] + (err * w as f32) / self.norm).clamp(0.0, 255.0);
+                    }
+                }
+
+                x = if x_step < 0 { x.wrapping_sub(1) } else { x + 1 };
+            }
+        }
+
+        out
+    }
+}
+
+/// A tiled threshold matrix, values in (0, 1).
+struct ThresholdMap {
+    size: usize,
+    thresholds: Vec<f32>,
+}
+
+impl ThresholdMap {
+    fn from_ranks(size: usize, ranks: &[usize]) -> Self {
+        let n = ranks.len() as f32;
+        ThresholdMap {
+            size,
+            thresholds: ranks.iter().map(|&r| (r as f32 + 0.5) / n).collect(),
+        }
+    }
+}
+
+struct Ordered {
+    map: &'static ThresholdMap,
+}
+
+impl Ditherer for Ordered {
+    fn dither(&self, gray: &[u8], width: usize, _height: usize, levels: usize) -> Vec<u8> {
+        assert!(levels >= 2, "levels must be >= 2");
+        let step = 255.0 / (levels - 1) as f32;
+        let size = self.map.size;
+
+        gray.iter()
+            .enumerate()
+            .map(|(i, &val)| {
+                let (x, y) = (i % width, i / width);
+                let t = self.map.thresholds[(y % size) * size + (x % size)];
+
+                // Round up to the next level once we're past this pixel's threshold
+                let q = val as f32 / step;
+                let k = q.floor() + if q.fract() > t { 1.0 } else { 0.0 };
+                (k as i32).clamp(0, (levels - 1) as i32) as u8
+            })
+            .collect()
+    }
+}
+
+fn bayer_map() -> &'static ThresholdMap {
+    static MAP: OnceLock<ThresholdMap> = OnceLock::new();
+    MAP.get_or_init(|| {
+        // Grow the 1x1 matrix by the usual [[4M, 4M+2], [4M+3, 4M+1]] rule
+        let mut size = 1;
+        let mut ranks = vec![0usize];
+        while size < 8 {
+            let next = size * 2;
+            let mut grown = vec![0usize; next * next];
+            for y in 0..next {
+                for x in 0..next {
+                    let base = 4 * ranks[(y % size) * size + (x % size)];
+                    let offset = match (x / size, y / size) {
+                        (0, 0) => 0,
+                        (1, 0) => 2,
+                        (0, _) => 3,
+                        _ => 1,
+                    };
+                    grown[y * next + x] = base + offset;
+                }
+            }
+            size = next;
+            ranks = grown;
+        }
+        ThresholdMap::from_ranks(size, &ranks)
+    })
+}
+
+fn blue_noise_map() -> &'static ThresholdMap {
+    static MAP: OnceLock<ThresholdMap> = OnceLock::new();
+    MAP.get_or_init(|| void_and_cluster(64, 1.5))
+}
+
+/// Ulichney's void-and-cluster method on a toroidal `size` x `size` grid.
+fn void_and_cluster(size: usize, sigma: f32) -> ThresholdMap {
+    let n = size * size;
+
+    // Gaussian falloff for every wrapped-around offset
+    let mut falloff = vec![0.0f32; n];
+    for dy in 0..size {
+        for dx in 0..size {
+            let wx = dx.min(size - dx) as f32;
+            let wy = dy.min(size - dy) as f32;
+            falloff[dy * size + dx] = (-(wx * wx + wy * wy) / (2.0 * sigma * sigma)).exp();
+        }
+    }
+
+    let update = |energy: &mut [f32], p: usize, sign: f32| {
+        let (px, py) = (p % size, p / size);
+        for y in 0..size {
+            for x in 0..size {
+                let dx = (x + size - px) % size;
+                let dy = (y + size - py) % size;
+                energy[y * size + x] += sign * falloff[dy * size + dx];
+            }
+        }
+    };
+    let tightest_cluster = |energy: &[f32], ones: &[bool]| {
+        (0..n)
+            .filter(|&p| ones[p])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+    let largest_void = |energy: &[f32], ones: &[bool]| {
+        (0..n)
+            .filter(|&p| !ones[p])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+
+    // Seed about 10% of the cells from a fixed xorshift so the mask is stable
+    let mut ones = vec![false; n];
+    let mut energy = vec![0.0f32; n];
+    let mut state: u32 = 0x9E37_79B9;
+    let mut seeded = 0;
+    while seeded < n / 10 {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        let p = state as usize % n;
+        if !ones[p] {
+            ones[p] = true;
+            update(&mut energy, p, 1.0);
+            seeded += 1;
+        }
+    }
+
+    // Move points from the tightest cluster into the largest void until stable
+    for _ in 0..n {
+        let cluster = tightest_cluster(&energy, &ones);
+        ones[cluster] = false;
+        update(&mut energy, cluster, -1.0);
+
+        let void = largest_void(&energy, &ones);
+        ones[void] = true;
+        update(&mut energy, void, 1.0);
+
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut ranks = vec![0usize; n];
+
+    // Rank the prototype's points by peeling off the tightest clusters...
+    let (mut peel_ones, mut peel_energy) = (ones.clone(), energy.clone());
+    for rank in (0..seeded).rev() {
+        let cluster = tightest_cluster(&peel_energy, &peel_ones);
+        peel_ones[cluster] = false;
+        update(&mut peel_energy, cluster, -1.0);
+        ranks[cluster] = rank;
+    }
+
+    // ...then rank the rest by filling the largest voids
+    for rank in seeded..n {
+        let void = largest_void(&energy, &ones);
+        ones[void] = true;
+        update(&mut energy, void, 1.0);
+        ranks[void] = rank;
+    }
+
+    ThresholdMap::from_ranks(size, &ranks)
+}
+
+fn pack_3bpp_high_first(idx: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let bytes_per_row = (width + 1) / 2;
+    let mut out = vec![0u8; bytes_per_row * height];
+    let mut oi = 0;
+
+    for y in 0..height {
+        let mut nibs = 0;
+        let mut byte = 0u8;
+
+        for x in 0..width {
+            let v3 = idx[y * width + x] & 0x07;
+            let nibble = (v3 << 1) & 0x0F;
+            byte = (byte << 4) | nibble;
+            nibs += 1;
+
+            if nibs == 2 {
+                out[oi] = byte;
+                oi += 1;
+                nibs = 0;
+                byte = 0;
+            }
+        }
+
+        if nibs == 1 {
+            out[oi] = byte << 4;
+            oi += 1;
+        }
+    }
+
+    out
+}
+
+fn unpack_3bpp_high_first(packed: &[u8], width: usize, height: usize) -> Vec<u8> {
+    let bytes_per_row = width.div_ceil(2);
+    let mut idx = Vec::with_capacity(width * height);
+
+    for y in 0..height {
+        let row = &packed[y * bytes_per_row..(y + 1) * bytes_per_row];
+
+        for x in 0..width {
+            let nibble = if x % 2 == 0 {
+                row[x / 2] >> 4
+            } else {
+                row[x / 2] & 0x0F
+            };
+            idx.push(nibble >> 1);
+        }
+    }
+
+    idx
+}
+
+/// Packs `bits`-wide levels (1, 2 or 4), first pixel in the high bits.
+fn pack_bits_high_first(idx: &[u8], width: usize, height: usize, bits: usize) -> Vec<u8> {
+    let per_byte = 8 / bits;
+    let bytes_per_row = width.div_ceil(per_byte);
+    let mask = ((1u16 << bits) - 1) as u8;
+    let mut out = vec![0u8; bytes_per_row * height];
+
+    for y in 0..height {
+        let row = &mut out[y * bytes_per_row..(y + 1) * bytes_per_row];
+
+        for x in 0..width {
+            let v = idx[y * width + x] & mask;
+            let slot = x % per_byte;
+            row[x / per_byte] |= v << (8 - bits * (slot + 1));
+        }
+    }
+
+    out
+}
+
+fn unpack_bits_high_first(packed: &[u8], width: usize, height: usize, bits: usize) -> Vec<u8> {
+    let per_byte = 8 / bits;
+    let bytes_per_row = width.div_ceil(per_byte);
+    let mask = ((1u16 << bits) - 1) as u8;
+    let mut idx = Vec::with_capacity(width * height);
+
+    for y in 0..height {
+        let row = &packed[y * bytes_per_row..(y + 1) * bytes_per_row];
+
+        for x in 0..width {
+            let slot = x % per_byte;
+            idx.push((row[x / per_byte] >> (8 - bits * (slot + 1))) & mask);
+        }
+    }
+
+    idx
+}
+
+fn apply_gamma(gray: &[u8], gamma: f32) -> Vec<u8> {
+    gray.iter()
+        .map(|&val| {
+            let normalized = val as f32 / 255.0;
+            let corrected = normalized.powf(gamma);
+            (corrected * 255.0).round() as u8
+        })
+        .collect()
+}
+
+/// ---- Partial refresh: frame metadata and deltas ----
+
+/// Written next to image.mz so the next run (and the device) knows what it holds.
+#[derive(Debug, Serialize, Deserialize)]
+struct FrameMeta {
+    width: usize,
+    height: usize,
+    format: OutputFormat,
+    /// FNV-1a of the packed (uncompressed) frame, as hex
+    hash: String,
+}
+
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for &b in bytes {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+/// Loads the previously written frame, if it's there, intact, and the same
+/// size and format as the one we're about to write.
+fn load_previous_frame(
+    out_dir: &FsPath,
+    width: usize,
+    height: usize,
+    format: OutputFormat,
+) -> Option<Vec<u8>> {
+    let meta: FrameMeta =
+        serde_json::from_str(&fs::read_to_string(out_dir.join("image.json")).ok()?).ok()?;
+    if meta.width != width || meta.height != height || meta.format != format {
+        println!("Previous frame was {:?}, not comparable", meta);
+        return None;
+    }
+
+    let packed = decompress_to_vec(&fs::read(out_dir.join("image.mz")).ok()?).ok()?;
+    if format!("{:016x}", fnv1a_64(&packed)) != meta.hash {
+        println!("Previous frame doesn't match its hash, ignoring it");
+        return None;
+    }
+
+    Some(packed)
+}
+
+/// A changed area, in panel pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct DirtyRect {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+/// Compares two packed frames tile by tile and merges the changed tiles into
+/// as few rectangles as is cheap: runs along each tile row, then runs with the
+/// same span on consecutive rows.
+fn dirty_rects(
+    prev: &[u8],
+    cur: &[u8],
+    width: usize,
+    height: usize,
+    layout: ByteLayout,
+    tile: usize,
+) -> Vec<DirtyRect> {
+    let row_bytes = layout.row_bytes(width);
+    let plane_len = row_bytes * height;
+
+    let tile_dirty = |tx: usize, ty: usize| {
+        let x = tx * tile;
+        let span = layout.byte_span(x, tile.min(width - x));
+        (ty * tile..((ty + 1) * tile).min(height)).any(|y| {
+            (0..layout.planes()).any(|plane| {
+                let row = plane * plane_len + y * row_bytes;
+                let range = row + span.start..row + span.end;
+                prev[range.clone()] != cur[range]
+            })
+        })
+    };
+
+    let mut rects: Vec<DirtyRect> = Vec::new();
+    let mut open: Vec<usize> = Vec::new(); // indices into rects still growing down
+
+    for ty in 0..height.div_ceil(tile) {
+        let y = ty * tile;
+        let tile_h = tile.min(height - y);
+        let mut still_open = Vec::new();
+
+        let mut tx = 0;
+        while tx < width.div_ceil(tile) {
+            if !tile_dirty(tx, ty) {
+                tx += 1;
+                continue;
+            }
+
+            let start = tx;
+            while tx < width.div_ceil(tile) && tile_dirty(tx, ty) {
+                tx += 1;
+            }
+
+            let x = start * tile;
+            let run_w = (tx * tile).min(width) - x;
+
+            // Extend the rect directly above if it covers exactly this run
+            match open
+                .iter()
+                .copied()
+                .find(|&i| rects[i].x == x && rects[i].width == run_w)
+            {
+                Some(i) => {
+                    rects[i].height += tile_h;
+                    still_open.push(i);
+                }
+                None => {
+                    rects.push(DirtyRect {
+                        x,
+                        y,
+                        width: run_w,
+                        height: tile_h,
+                    });
+                    still_open.push(rects.len() - 1);
+                }
+            }
+        }
+
+        open = still_open;
+    }
+
+    rects
+}
+
+/// Builds the uncompressed delta file. Everything is little-endian:
+///
+/// ```text
+/// "IKD1"  u8 flags (1 = full refresh needed, no rects follow)
+/// u8 bits per pixel, u8 planes, u16 width, u16 height
+/// u64 hash of the frame this applies on top of, u64 hash of the new frame
+/// u16 rect count, then per rect: u16 x, y, w, h and, for each plane,
+/// the packed bytes of each of its rows
+/// ```
+fn encode_delta(
+    prev: Option<&[u8]>,
+    cur: &[u8],
+    width: usize,
+    height: usize,
+    layout: ByteLayout,
+    tile: usize,
+) -> (Vec<u8>, Option<usize>) {
+    let rects = prev
+        .map(|prev| dirty_rects(prev, cur, width, height, layout, tile))
+        .unwrap_or_default();
+
+    // Once most of the panel changed a full refresh is cheaper anyway
+    let dirty_area: usize = rects.iter().map(|r| r.width * r.height).sum();
+    let full = prev.is_none() || dirty_area * 2 > width * height || rects.len() > u16::MAX as usize;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(b"IKD1");
+    out.push(full as u8);
+    out.push(layout.bits() as u8);
+    out.push(layout.planes() as u8);
+    out.extend_from_slice(&(width as u16).to_le_bytes());
+    out.extend_from_slice(&(height as u16).to_le_bytes());
+    out.extend_from_slice(&prev.map(fnv1a_64).unwrap_or(0).to_le_bytes());
+    out.extend_from_slice(&fnv1a_64(cur).to_le_bytes());
+
+    if full {
+        out.extend_from_slice(&0u16.to_le_bytes());
+        return (out, None);
+    }
+
+    let row_bytes = layout.row_bytes(width);
+    let plane_len = row_bytes * height;
+
+    out.extend_from_slice(&(rects.len() as u16).to_le_bytes());
+    for rect in &rects {
+        for v in [rect.x, rect.y, rect.width, rect.height] {
+            out.extend_from_slice(&(v as u16).to_le_bytes());
+        }
+
+        let span = layout.byte_span(rect.x, rect.width);
+        for plane in 0..layout.planes() {
+            for y in rect.y..rect.y + rect.height {
+                let row = plane * plane_len + y * row_bytes;
+                out.extend_from_slice(&cur[row + span.start..row + span.end]);
+            }
+        }
+    }
+
+    (out, Some(rects.len()))
+}
+
+/// Writes to a temp file in the same directory, syncs, then renames over `path`.
+fn write_atomic(path: &FsPath, bytes: &[u8]) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let tmp = path.with_file_name(format!(
+        ".{}.tmp.{}",
+        name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    {
+        let mut file = File::create(&tmp)?;
+        file.write_all(bytes)?;
+        file.sync_all()?;
+    }
+
+    fs::rename(&tmp, path)
+}
+
+/// ---- Fetchers: produce the envelope files the renderer reads ----
+
+const OPEN_METEO_URL: &str = "https://api.open-meteo.com/v1/forecast";
+
+// Exactly the variables WeatherResponse and friends deserialize
+const OPEN_METEO_CURRENT: &str =
+    "temperature_2m,apparent_temperature,weather_code,relative_humidity_2m";
+const OPEN_METEO_HOURLY: &str =
+    "temperature_2m,weather_code,precipitation,precipitation_probability";
+const OPEN_METEO_DAILY: &str =
+    "weather_code,temperature_2m_max,temperature_2m_min,precipitation_probability_max";
+
+/// `"weather"` section of the `--config` file.
+#[derive(Debug, Clone, Deserialize)]
+struct WeatherLocation {
+    latitude: f64,
+    longitude: f64,
+    #[serde(default = "default_temperature_unit")]
+    temperature_unit: String,
+    /// IANA name, or "auto" to use the location's own zone
+    #[serde(default = "default_weather_timezone")]
+    timezone: String,
+    #[serde(flatten)]
+    policy: FetchPolicy,
+}
+
+fn default_temperature_unit() -> String {
+    "fahrenheit".to_string()
+}
+
+fn default_weather_timezone() -> String {
+    "auto".to_string()
+}
+
+/// Asks Open-Meteo for a forecast and checks it parses as a `WeatherResponse`
+/// before handing back the raw JSON.
+fn request_open_meteo(base_url: &str, location: &WeatherLocation) -> Result<Value, String> {
+    let response = ureq::get(base_url)
+        .query("latitude", &location.latitude.to_string())
+        .query("longitude", &location.longitude.to_string())
+        .query("current", OPEN_METEO_CURRENT)
+        .query("hourly", OPEN_METEO_HOURLY)
+        .query("daily", OPEN_METEO_DAILY)
+        .query("temperature_unit", &location.temperature_unit)
+        .query("timezone", &location.timezone)
+        .timeout(Duration::from_secs(location.policy.timeout_secs))
+        .call()
+        .map_err(|e| format!("Open-Meteo request failed: {}", e))?;
+
+    let body = response
+        .into_string()
+        .map_err(|e| format!("Failed to read Open-Meteo response: {}", e))?;
+    let value: Value = serde_json::from_str(&body)
+        .map_err(|e| format!("Open-Meteo returned invalid JSON: {}", e))?;
+
+    serde_json::from_value::<WeatherResponse>(value.clone())
+        .map_err(|e| format!("Open-Meteo response is missing fields: {}", e))?;
+
+    Ok(value)
+}
+
+/// Retry and expiry settings, shared by the weather fetcher and `"feeds"` entries.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+struct FetchPolicy {
+    /// Extra attempts after the first one fails
+    retries: u32,
+    /// Wait before the first retry; doubles after each one
+    retry_delay_secs: u64,
+    /// Per-request timeout for URL sources
+    timeout_secs: u64,
+    /// How long a successful fetch stays fresh, written as `expires`
+    expires_minutes: Option<i64>,
+}
+
+impl Default for FetchPolicy {
+    fn default() -> Self {
+        FetchPolicy {
+            retries: 2,
+            retry_delay_secs: 5,
+            timeout_secs: 30,
+            expires_minutes: None,
+        }
+    }
+}
+
+/// A `"feeds"` entry: `<name>.json` comes from either a URL returning JSON or
+/// a command printing JSON on stdout.
+#[derive(Debug, Deserialize)]
+struct FeedSource {
+    url: Option<String>,
+    /// Program and arguments, run without a shell
+    command: Option<Vec<String>>,
+    #[serde(flatten)]
+    policy: FetchPolicy,
+}
+
+/// Writes `StateEnvelope` files. On failure the previous file's `last_good`
+/// is carried over so the display keeps showing the last data that worked.
+struct EnvelopeWriter {
+    path: PathBuf,
+    expires_after: Option<chrono::Duration>,
+}
+
+impl EnvelopeWriter {
+    fn new(path: PathBuf, policy: &FetchPolicy) -> Self {
+        EnvelopeWriter {
+            path: path,
+            expires_after: policy.expires_minutes.map(chrono::Duration::minutes),
+        }
+    }
+
+    fn previous(&self) -> Option<StateEnvelope> {
+        let contents = fs::read_to_string(&self.path).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    fn write(&self, result: &Result<Value, String>) -> io::Result<()> {
+        let now = Utc::now();
+
+        let envelope = match result {
+            Ok(data) => StateEnvelope {
+                status: "ok".to_string(),
+                fetched_at: now,
+                error: None,
+                last_good: Some(LastGood {
+                    fetched_at: now,
+                    expires: self.expires_after.map(|after| now + after),
+                    data: data.clone(),
+                }),
+            },
+            Err(error) => StateEnvelope {
+                status: "error".to_string(),
+                fetched_at: now,
+                error: Some(error.clone()),
+                last_good: self.previous().and_then(|envelope| envelope.last_good),
+            },
+        };
+
+        write_atomic(
+            &self.path,
+            serde_json::to_string_pretty(&envelope)?.as_bytes(),
+        )
+    }
+}
+
+/// Calls `attempt` until it succeeds or the policy's retries run out.
+fn fetch_with_retries(
+    label: &str,
+    policy: &FetchPolicy,
+    mut attempt: impl FnMut() -> Result<Value, String>,
+) -> Result<Value, String> {
+    let mut delay = Duration::from_secs(policy.retry_delay_secs);
+
+    for tries_left in (0..=policy.retries).rev() {
+        match attempt() {
+            Ok(value) => return Ok(value),
+            Err(e) if tries_left == 0 => return Err(e),
+            Err(e) => {
+                eprintln!(
+                    "{}: {} (retrying in {}s, {} left)",
+                    label,
+                    e,
+                    delay.as_secs(),
+                    tries_left
+                );
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+        }
+    }
+
+    unreachable!("the last attempt always returns")
+}
+
+fn fetch_json_url(url: &str, timeout: Duration) -> Result<Value, String> {
+    let response = ureq::get(url)
+        .timeout(timeout)
+        .call()
+        .map_err(|e| format!("Request failed: {}", e))?;
+    let body = response
+        .into_string()
+        .map_err(|e| format!("Failed to read response: {}", e))?;
+
+    serde_json::from_str(&body).map_err(|e| format!("Response is not valid JSON: {}", e))
+}
+
+fn run_feed_command(command: &[String]) -> Result<Value, String> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| "Empty command".to_string())?;
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| format!("Failed to run {}: {}", program, e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(match stderr.trim() {
+            "" => format!("{} exited with {}", program, output.status),
+            stderr => format!("{} exited with {}: {}", program, output.status, stderr),
+        });
+    }
+
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("{} printed invalid JSON: {}", program, e))
+}
+
+/// Rejects payloads the renderer couldn't read, so they never replace `last_good`.
+fn validate_feed(name: &str, data: &Value) -> Result<(), String> {
+    fn check<T: DeserializeOwned>(data: &Value) -> Result<(), String> {
+        serde_json::from_value::<T>(data.clone())
+            .map(|_| ())
+            .map_err(|e| format!("Unexpected data: {}", e))
+    }
+
+    match name {
+        "weather" => check::<WeatherResponse>(data),
+        "cleaning" => check::<Vec<DailyScore>>(data),
+        "balances" => check::<Vec<PersonBalance>>(data),
+        "names" => check::<Vec<PersonName>>(data),
+        "upcoming_payouts" => check::<Vec<UpcomingPayout>>(data),
+        "todo" => check::<Vec<TodoItem>>(data),
+        "battery" => check::<BatteryReport>(data),
+        _ => Ok(()),
+    }
+}
+
+/// Fetches one `"feeds"` entry into `<data_dir>/<name>.json`.
+fn fetch_feed(
+    name: &str,
+    source: &FeedSource,
+    data_dir: &FsPath,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(format!("Invalid feed name '{}'", name).into());
+    }
+    // dates.json is a plain list, not an envelope, so writing one would
+    // leave the countdown with nothing it can read
+    if name == "dates" {
+        return Err("dates.json is edited by hand and can't be fetched".into());
+    }
+
+    let policy = &source.policy;
+    let timeout = Duration::from_secs(policy.timeout_secs);
+    let mut attempt = || -> Result<Value, String> {
+        let data = match (&source.url, &source.command) {
+            (Some(url), None) => fetch_json_url(url, timeout)?,
+            (None, Some(command)) => run_feed_command(command)?,
+            _ => return Err("Feed needs exactly one of \"url\" or \"command\"".to_string()),
+        };
+        validate_feed(name, &data)?;
+        Ok(data)
+    };
+
+    fs::create_dir_all(data_dir)?;
+    let writer = EnvelopeWriter::new(data_dir.join(format!("{}.json", name)), policy);
+
+    println!("Fetching {}", name);
+    let result = fetch_with_retries(name, policy, &mut attempt);
+    writer.write(&result)?;
+
+    match result {
+        Ok(_) => {
+            println!("Saved {}", writer.path.display());
+            Ok(())
+        }
+        Err(e) => Err(format!("{}: {} (kept last_good)", name, e).into()),
+    }
+}
+
+/// `fetch all` / `fetch <name>...`: keeps going past failures and reports
+/// the first one at the end.
+fn fetch_feeds(
+    names: &[String],
+    config: &ConfigFile,
+    data_dir: &FsPath,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut first_error: Option<Box<dyn std::error::Error>> = None;
+
+    for name in names {
+        let result = match (name.as_str(), config.feeds.get(name)) {
+            (_, Some(source)) => fetch_feed(name, source, data_dir),
+            ("weather", None) if config.weather.is_some() => fetch_weather(
+                &WeatherFetchArgs {
+                    latitude: None,
+                    longitude: None,
+                    base_url: OPEN_METEO_URL.to_string(),
+                },
+                config.weather.as_ref(),
+                data_dir,
+            ),
+            _ => Err(format!("No feed named '{}' in the config", name).into()),
+        };
+
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            first_error.get_or_insert(e);
+        }
+    }
+
+    match first_error {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// `fetch weather`: CLI coordinates win over the config file's.
+fn fetch_weather(
+    fetch: &WeatherFetchArgs,
+    config: Option<&WeatherLocation>,
+    data_dir: &FsPath,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let latitude = fetch.latitude.or(config.map(|c| c.latitude));
+    let longitude = fetch.longitude.or(config.map(|c| c.longitude));
+    let (Some(latitude), Some(longitude)) = (latitude, longitude) else {
+        return Err(
+            "fetch weather needs --latitude/--longitude or a \"weather\" section in --config"
+                .into(),
+        );
+    };
+    if !(-90.0..=90.0).contains(&latitude) || !(-180.0..=180.0).contains(&longitude) {
+        return Err(format!("Invalid coordinates {}, {}", latitude, longitude).into());
+    }
+
+    let location = WeatherLocation {
+        latitude: latitude,
+        longitude: longitude,
+        temperature_unit: config
+            .map(|c| c.temperature_unit.clone())
+            .unwrap_or_else(default_temperature_unit),
+        timezone: config
+            .map(|c| c.timezone.clone())
+            .unwrap_or_else(default_weather_timezone),
+        policy: config.map(|c| c.policy.clone()).unwrap_or_default(),
+    };
+
+    fs::create_dir_all(data_dir)?;
+    let writer = EnvelopeWriter::new(data_dir.join("weather.json"), &location.policy);
+
+    println!(
+        "Fetching weather for {}, {} from {}",
+        location.latitude, location.longitude, fetch.base_url
+    );
+    let result = fetch_with_retries("weather", &location.policy, || {
+        request_open_meteo(&fetch.base_url, &location)
+    });
+    writer.write(&result)?;
+
+    match result {
+        Ok(_) => {
+            println!("Saved {}", writer.path.display());
+            Ok(())
+        }
+        Err(e) => Err(format!("{} (kept last_good in {})", e, writer.path.display()).into()),
+    }
+}
+
+/// ---- HTTP server: hands the latest frame to the display ----
+
+/// What a display told us about itself, kept per profile. Each field holds
+/// the latest request that carried it.
+#[derive(Debug, Default, Clone, Serialize)]
+struct DeviceReport {
+    last_seen: Option<DateTime<Utc>>,
+    remote_addr: Option<String>,
+    firmware: Option<String>,
+    battery_percent: Option<f64>,
+    charging: Option<bool>,
+    voltage: Option<f64>,
+    /// When a request last carried any of the battery fields
+    battery_reported_at: Option<DateTime<Utc>>,
+}
+
+impl DeviceReport {
+    /// Takes whichever fields `param` finds, keeping the rest as they were.
+    /// Returns true if any battery field came in.
+    fn update(
+        &mut self,
+        param: impl Fn(&str, &'static str) -> Option<String>,
+        now: DateTime<Utc>,
+    ) -> bool {
+        let battery = param("battery", "X-Battery-Percent").and_then(|v| v.trim().parse().ok());
+        let charging = param("charging", "X-Battery-Charging").and_then(|v| parse_flag(&v));
+        let voltage = param("voltage", "X-Battery-Voltage").and_then(|v| v.trim().parse().ok());
+        let firmware = param("fw", "X-Firmware-Version").filter(|v| !v.is_empty());
+
+        self.last_seen = Some(now);
+        if battery.is_some() {
+            self.battery_percent = battery;
+        }
+        if charging.is_some() {
+            self.charging = charging;
+        }
+        if voltage.is_some() {
+            self.voltage = voltage;
+        }
+        if firmware.is_some() {
+            self.firmware = firmware;
+        }
+
+        let battery_reported = battery.is_some() || charging.is_some() || voltage.is_some();
+        if battery_reported {
+            self.battery_reported_at = Some(now);
+        }
+        battery_reported
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ServerStatus<'a> {
+    server_time: DateTime<Utc>,
+    frame: Option<FrameMeta>,
+    rendered_at: Option<DateTime<Utc>>,
+    device: &'a DeviceReport,
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                // from_str_radix would also take a sign, as in "%+1"
+                let hex = &bytes[i + 1..i + 3];
+                match std::str::from_utf8(hex)
+                    .ok()
+                    .filter(|_| hex.iter().all(u8::is_ascii_hexdigit))
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                {
+                    Some(b) => {
+                        out.push(b);
+                        i += 2;
+                    }
+                    None => out.push(b'%'),
+                }
+            }
+            b => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn parse_query(url: &str) -> HashMap<String, String> {
+    url.split_once('?')
+        .map(|(_, query)| query)
+        .unwrap_or("")
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key), percent_decode(value))
+        })
+        .collect()
+}
 
-        let index = i - hourly_start_index;
-        // let pct = index as f32 / (num_hours - 1) as f32;
-        // println!(" >> {} -- {}", weather.hourly.time[i], pct);
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" => Some(true),
+        "0" | "false" | "no" => Some(false),
+        _ => None,
+    }
+}
 
-        // Parse as a naive datetime (no timezone)
-        let dt = NaiveDateTime::parse_from_str(&weather.hourly.time[i], "%Y-%m-%dT%H:%M")
-            .expect("Failed to parse datetime");
+/// Picks up battery and firmware details from either query parameters
+/// (`battery`, `charging`, `voltage`, `fw`) or `X-Battery-Percent`,
+/// `X-Battery-Charging`, `X-Battery-Voltage` and `X-Firmware-Version` headers.
+/// Returns true if the request carried a battery reading.
+fn update_device_report(report: &mut DeviceReport, request: &tiny_http::Request) -> bool {
+    let query = parse_query(request.url());
+    let header = |name: &'static str| {
+        request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv(name))
+            .map(|h| h.value.as_str().to_string())
+    };
+    let param = |key: &str, header_name: &'static str| {
+        query.get(key).cloned().or_else(|| header(header_name))
+    };
 
-        // Format as 12-hour with AM/PM
-        let formatted = dt.format("%-I %p").to_string(); // %-I = hour without leading zero
-        // let formatted = dt.format("%-I").to_string();
+    report.remote_addr = request.remote_addr().map(|a| a.to_string());
+    report.update(param, Utc::now())
+}
 
-        // println!("{formatted}"); // "12 AM"
+/// Stores a panel's battery report as its battery-<profile>.json feed,
+/// dated when the battery reading came in.
+fn write_battery_envelope(
+    data_dir: &FsPath,
+    profile: &str,
+    report: &DeviceReport,
+) -> io::Result<()> {
+    let (Some(percent), Some(now)) = (report.battery_percent, report.battery_reported_at) else {
+        return Ok(());
+    };
 
-        if (index + 3) % 4 == 0 {
-            draw_text_blob_with_color(
-                canvas,
-                &mini_font,
-                (hourly_x_start as f32 + index as f32 * hourly_slot_width) as i32,
-                y + today_offset,
-                &formatted,
-                Color::BLACK,
-                0.5,
+    let battery = BatteryReport {
+        percent,
+        charging: report.charging.unwrap_or(false),
+        voltage: report.voltage,
+        reported_at: now,
+        firmware: report.firmware.clone(),
+    };
+    let envelope = StateEnvelope {
+        status: "ok".to_string(),
+        fetched_at: now,
+        error: None,
+        last_good: Some(LastGood {
+            fetched_at: now,
+            expires: None,
+            data: serde_json::to_value(&battery)?,
+        }),
+    };
+
+    write_atomic(
+        &data_dir.join(format!("battery-{}.json", profile)),
+        serde_json::to_string_pretty(&envelope)?.as_bytes(),
+    )
+}
+
+fn read_frame_meta(out_dir: &FsPath) -> Option<FrameMeta> {
+    serde_json::from_str(&fs::read_to_string(out_dir.join("image.json")).ok()?).ok()
+}
+
+/// True if any entity tag in an If-None-Match header matches `etag`.
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == "*" || tag == etag)
+}
+
+fn header(name: &str, value: &str) -> tiny_http::Header {
+    tiny_http::Header::from_bytes(name.as_bytes(), value.as_bytes())
+        .expect("header names and values are ASCII")
+}
+
+/// Splits `/kitchen/image.mz` into the kitchen profile's name and out_dir
+/// and `/image.mz`. Paths without a profile name go to the first profile.
+fn route_profile<'a>(
+    path: &'a str,
+    outputs: &'a [(String, PathBuf)],
+) -> (&'a str, &'a FsPath, &'a str) {
+    if let Some((name, rest)) = path.trim_start_matches('/').split_once('/')
+        && let Some((name, out_dir)) = outputs.iter().find(|(n, _)| n == name)
+    {
+        return (name, out_dir, &path[path.len() - rest.len() - 1..]);
+    }
+
+    (&outputs[0].0, &outputs[0].1, path)
+}
+
+fn handle_request(
+    request: tiny_http::Request,
+    outputs: &[(String, PathBuf)],
+    data_dir: &FsPath,
+    devices: &mut HashMap<String, DeviceReport>,
+) -> io::Result<()> {
+    let path = request.url().split('?').next().unwrap_or("/").to_string();
+    let (profile, out_dir, path) = route_profile(&path, outputs);
+
+    let device = devices.entry(profile.to_string()).or_default();
+    if update_device_report(device, &request)
+        && let Err(e) = write_battery_envelope(data_dir, profile, device)
+    {
+        eprintln!("Failed to store {}'s battery report: {}", profile, e);
+    }
+
+    println!(
+        "HTTP {} {} from {}",
+        request.method(),
+        path,
+        device.remote_addr.as_deref().unwrap_or("?")
+    );
+
+    if *request.method() != tiny_http::Method::Get && *request.method() != tiny_http::Method::Head {
+        return request.respond(tiny_http::Response::empty(405));
+    }
+
+    let (file, content_type) = match path {
+        "/status" => {
+            let meta = read_frame_meta(out_dir);
+            let rendered_at = fs::metadata(out_dir.join("image.mz"))
+                .and_then(|m| m.modified())
+                .ok()
+                .map(DateTime::<Utc>::from);
+            let status = ServerStatus {
+                server_time: Utc::now(),
+                frame: meta,
+                rendered_at,
+                device,
+            };
+            let body = serde_json::to_string_pretty(&status)?;
+            return request.respond(
+                tiny_http::Response::from_string(body)
+                    .with_header(header("Content-Type", "application/json")),
             );
         }
+        "/image.mz" => ("image.mz", "application/octet-stream"),
+        "/image.delta.mz" => ("image.delta.mz", "application/octet-stream"),
+        "/output.png" => ("output.png", "image/png"),
+        _ => return request.respond(tiny_http::Response::empty(404)),
+    };
+
+    // Every file describes the same frame, so they share its hash as the ETag
+    let etag = read_frame_meta(out_dir).map(|meta| format!("\"{}\"", meta.hash));
+
+    if let Some(etag) = &etag
+        && request
+            .headers()
+            .iter()
+            .find(|h| h.field.equiv("If-None-Match"))
+            .is_some_and(|h| etag_matches(h.value.as_str(), etag))
+    {
+        return request.respond(tiny_http::Response::empty(304).with_header(header("ETag", etag)));
     }
 
-    // temperature curve for today
-    let today_temp_range = get_temp_range(&temp_points);
-    draw_text_blob(
-        canvas,
-        &font_boss.emoji_font,
-        x + 10,
-        y + today_offset + 60,
-        "🌡️",
-    );
-    draw_hourly(
-        canvas,
-        &mini_font,
-        hourly_x_start,
-        y + today_offset + 10,
-        hourly_width,
-        hourly_height,
-        &temp_points,
-        "°",
-        today_temp_range,
-    );
+    let bytes = match fs::read(out_dir.join(file)) {
+        Ok(bytes) => bytes,
+        Err(_) => return request.respond(tiny_http::Response::empty(404)),
+    };
 
-    draw_text_blob(
-        canvas,
-        &font_boss.emoji_font,
-        x + 10,
-        y + today_offset + hourly_height + 30 + 60,
-        "💧",
-    );
-    draw_hourly(
-        canvas,
-        &mini_font,
-        hourly_x_start,
-        y + today_offset + hourly_height + 40,
-        hourly_width,
-        hourly_height,
-        &precip_points,
-        "%",
-        (0.0, 100.0),
-    );
+    let mut response = tiny_http::Response::from_data(bytes)
+        .with_header(header("Content-Type", content_type))
+        .with_header(header("Cache-Control", "no-cache"));
+    if let Some(etag) = &etag {
+        response = response.with_header(header("ETag", etag));
+    }
 
-    if let Some(daily) = &weather.daily {
-        let day_width = 102.0;
+    request.respond(response)
+}
 
-        let num_daily_pts = daily.time.len().min(7);
+/// Binds `addr` and serves each profile's output directory from a background
+/// thread, keyed by profile name.
+fn spawn_server(
+    addr: &str,
+    outputs: Vec<(String, PathBuf)>,
+    data_dir: PathBuf,
+) -> Result<std::thread::JoinHandle<()>, Box<dyn std::error::Error>> {
+    let server = tiny_http::Server::http(addr)
+        .map_err(|e| format!("Failed to listen on {}: {}", addr, e))?;
+    for (name, out_dir) in &outputs {
+        println!("Serving {} on http://{}/{}/", out_dir.display(), addr, name);
+    }
 
-        let mut max_temp: f32 = -99999999.0;
-        let mut min_temp: f32 = 99999999.0;
-        for i in 0..num_daily_pts {
-            max_temp = max_temp.max(daily.temperature_max[i]);
-            min_temp = min_temp.min(daily.temperature_max[i]);
-            max_temp = max_temp.max(daily.temperature_min[i]);
-            min_temp = min_temp.min(daily.temperature_min[i]);
+    Ok(std::thread::spawn(move || {
+        let mut devices = HashMap::new();
+        for request in server.incoming_requests() {
+            if let Err(e) = handle_request(request, &outputs, &data_dir, &mut devices) {
+                eprintln!("HTTP error: {}", e);
+            }
         }
-        let temp_range = max_temp - min_temp;
+    }))
+}
 
-        let max_daily_vpixels_allowed = 105;
-        let pixels_per_degree = max_daily_vpixels_allowed as f32 / temp_range;
+/// Convert any path-like type to a fully qualified absolute path as a String
+fn absolute_path_string<P: AsRef<std::path::Path>>(path: P) -> io::Result<String> {
+    let abs_path = std::fs::canonicalize(path.as_ref())?;
+    abs_path.into_os_string().into_string().map_err(|os_str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Failed to convert path to string: {:?}", os_str),
+        )
+    })
+}
 
-        println!("min max  {} {}", max_temp, min_temp);
-        println!("max degree range {}", temp_range);
-        println!("max pixels {}", max_daily_vpixels_allowed);
-        println!("pixels per degree {}", pixels_per_degree);
+/// ---- Decode: image.mz back to a viewable PNG ----
 
-        for i in 0..num_daily_pts {
-            // Parse the string into a NaiveDate
-            let date = NaiveDate::parse_from_str(&daily.time[i], "%Y-%m-%d").expect("Invalid date");
+/// Level indices as the panel shows them: evenly spaced grays, and red for
+/// the red level.
+fn level_pixels(encoder: &dyn FrameEncoder, levels: &[u8]) -> Vec<[u8; 4]> {
+    let white = (encoder.levels() - 1).max(1);
 
-            // Get the weekday (0 = Monday, 6 = Sunday if you want ISO, or 0 = Sunday with .num_days_from_sunday())
-            let weekday = date.weekday().num_days_from_sunday();
+    levels
+        .iter()
+        .map(|&level| {
+            if encoder.has_red() && level as usize == encoder.levels() {
+                [200, 30, 30, 255]
+            } else {
+                let v = (level as usize * 255 / white) as u8;
+                [v, v, v, 255]
+            }
+        })
+        .collect()
+}
 
-            let px = x as f32 + 62.0 + i as f32 * day_width;
-            let x = px as i32 - 10;
-            let y = y as i32 + 350;
+fn rgba_image(
+    pixels: &[[u8; 4]],
+    width: usize,
+    height: usize,
+) -> Result<Image, Box<dyn std::error::Error>> {
+    let image_info = ImageInfo::new(
+        (width as i32, height as i32),
+        ColorType::RGBA8888,
+        AlphaType::Premul,
+        None,
+    );
+    let image = Image::from_raster_data(
+        &image_info,
+        Data::new_copy(pixels.as_flattened()),
+        width * 4,
+    )
+    .ok_or("Failed to create image from pixels")?;
 
-            let qdiff = max_temp - daily.temperature_max[i] as f32;
+    Ok(image)
+}
 
-            let vpushdown = qdiff * pixels_per_degree;
+/// `decode`: inflates a packed frame and writes its levels out as a PNG, in
+/// the panel's own orientation.
+fn decode_frame(input: &FsPath, output: &FsPath) -> Result<(), Box<dyn std::error::Error>> {
+    let meta_path = input.with_extension("json");
+    let meta: FrameMeta = serde_json::from_str(
+        &fs::read_to_string(&meta_path)
+            .map_err(|e| format!("Failed to read {}: {}", meta_path.display(), e))?,
+    )
+    .map_err(|e| format!("Failed to parse {}: {}", meta_path.display(), e))?;
+
+    let compressed =
+        fs::read(input).map_err(|e| format!("Failed to read {}: {}", input.display(), e))?;
+    let packed = decompress_to_vec(&compressed)
+        .map_err(|e| format!("Failed to inflate {}: {:?}", input.display(), e))?;
+
+    let encoder = meta.format.encoder();
+    let expected = encoder.packed_len(meta.width, meta.height);
+    if packed.len() != expected {
+        return Err(format!(
+            "{} holds {} bytes, but a {}x{} {:?} frame is {}",
+            input.display(),
+            packed.len(),
+            meta.width,
+            meta.height,
+            meta.format,
+            expected
+        )
+        .into());
+    }
+    if format!("{:016x}", fnv1a_64(&packed)) != meta.hash {
+        println!(
+            "Warning: {} doesn't match the hash in {}",
+            input.display(),
+            meta_path.display()
+        );
+    }
 
-            println!(
-                "   {}: {}-{}, {} -> {}",
-                i, daily.temperature_min[i], daily.temperature_max[i], qdiff, vpushdown
-            );
+    let levels = encoder.unpack(&packed, meta.width, meta.height);
+    let image = rgba_image(&level_pixels(&*encoder, &levels), meta.width, meta.height)?;
+    let data = image
+        .encode_to_data(skia_safe::EncodedImageFormat::PNG)
+        .ok_or("Failed to encode image")?;
+    fs::write(output, data.as_bytes())?;
 
-            let this_grad_off = vpushdown as i32;
-            let this_daily_height =
-                (pixels_per_degree * (daily.temperature_max[i] - daily.temperature_min[i])) as i32;
+    println!(
+        "Decoded {}x{} {:?} frame to {}",
+        meta.width,
+        meta.height,
+        meta.format,
+        output.display()
+    );
+    Ok(())
+}
 
-            draw_text_blob_with_color(
-                canvas,
-                &mini_font,
-                x,
-                y + this_grad_off + 2,
-                &format!("{}°", daily.temperature_max[i].round()),
-                Color::BLACK,
-                0.5,
-            );
+/// ---- Preview: re-render on every change and show it in a browser ----
+
+/// What the preview page polls; `version` goes up on every render attempt.
+#[derive(Debug, Default, Serialize)]
+struct PreviewStatus {
+    version: u64,
+    profile: String,
+    layout: String,
+    rendered_at: Option<DateTime<Local>>,
+    render_ms: Option<u128>,
+    error: Option<String>,
+    issues: Vec<String>,
+}
 
-            let grad_half_width = 10;
-            draw_temp_gradient(
-                canvas,
-                x - grad_half_width,
-                y + this_grad_off + 10,
-                grad_half_width * 2,
-                this_daily_height,
-            );
+/// The latest render, shared with the server thread. The images stay at the
+/// last good render while the layout or config is broken.
+#[derive(Default)]
+struct PreviewState {
+    status: PreviewStatus,
+    raw_png: Vec<u8>,
+    panel_png: Vec<u8>,
+}
 
-            draw_text_blob_with_color(
-                canvas,
-                &mini_font,
-                x,
-                y + this_grad_off + this_daily_height + 28,
-                &format!("{}°", daily.temperature_min[i].round()),
-                Color::BLACK,
-                0.5,
-            );
+const PREVIEW_PAGE: &str = r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>inkhols preview</title>
+<style>
+  body { font-family: sans-serif; background: #ddd; margin: 16px; }
+  .frames { display: flex; flex-wrap: wrap; gap: 16px; }
+  figure { margin: 0; }
+  img { max-width: 100%; background: #fff; border: 1px solid #888; }
+  #error { color: #b00; white-space: pre-wrap; }
+  #issues { white-space: pre-wrap; }
+</style>
+</head>
+<body>
+<p id="status">Waiting for the first render…</p>
+<p id="error"></p>
+<pre id="issues"></pre>
+<div class="frames">
+  <figure><figcaption>Skia render</figcaption><img id="raw" alt=""></figure>
+  <figure><figcaption>Simulated panel</figcaption><img id="panel" alt=""></figure>
+</div>
+<script>
+let version = null;
+async function poll() {
+  try {
+    const status = await (await fetch("/status")).json();
+    if (status.version !== version) {
+      version = status.version;
+      document.getElementById("raw").src = "/raw.png?v=" + version;
+      document.getElementById("panel").src = "/panel.png?v=" + version;
+      document.getElementById("status").textContent =
+        status.profile + " (" + status.layout + ")" +
+        (status.rendered_at ? ", rendered " + status.rendered_at : "") +
+        (status.render_ms !== null && !status.error ? " in " + status.render_ms + "ms" : "");
+      document.getElementById("error").textContent = status.error || "";
+      document.getElementById("issues").textContent = status.issues.join("\n");
+    }
+  } catch (e) {
+    document.getElementById("status").textContent = "Preview server isn't responding";
+  }
+  setTimeout(poll, 1000);
+}
+poll();
+</script>
+</body>
+</html>
+"#;
+
+/// The config entry `preview` shows: the named or first profile, or None to
+/// use layout.json and the flags when the config doesn't list any.
+fn preview_target<'a>(
+    args: &Args,
+    config: &'a ConfigFile,
+    name: Option<&str>,
+) -> Result<Option<&'a ProfileConfig>, String> {
+    if args.config.is_none() {
+        return match name {
+            Some(_) => Err("--profile needs --config".to_string()),
+            None => Ok(None),
+        };
+    }
 
-            let precip_text = format!("{}%", daily.precipitation_probability[i].round());
+    match name {
+        Some(name) => config
+            .profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .map(Some)
+            .ok_or_else(|| format!("No profile named '{}'", name)),
+        None => Ok(config.profiles.first()),
+    }
+}
 
-            let svg_width = 25;
-            let label_margin = 7.0;
-            let precip_height = max_daily_vpixels_allowed + 30;
-            let day_label_width =
-                svg_width as f32 + mini_font.measure_str(&precip_text, None).0 + 5.0;
-            let half_width = day_label_width * 0.5;
-            let label_start = x as f32 - half_width;
+/// What the panel will show: the dithered levels as grays (red stays red),
+/// turned back to the layout's orientation.
+fn panel_view(profile: &Profile, frame: &Frame) -> Result<Image, Box<dyn std::error::Error>> {
+    let pixels = level_pixels(&*profile.output_format.encoder(), &frame.levels);
 
-            let svg = code_to_svg(daily.weather_code[i], svg_width);
-            canvas.draw_image(
-                &svg.unwrap().image,
-                (label_start, (y + precip_height) as f32 + 20.0),
-                None,
-            );
+    let panel = &profile.panel;
+    let pixels = rotate_buffer(&pixels, panel.width, panel.height, panel.rotation.inverse());
+    let (width, height) = panel.canvas_size();
 
-            draw_text_blob_with_color(
-                canvas,
-                &mini_font,
-                (label_start + label_margin) as i32 + svg_width as i32,
-                y + precip_height + 39,
-                &precip_text,
-                Color::BLACK,
-                0.0,
-            );
+    rgba_image(&pixels, width, height)
+}
 
-            draw_text_blob_with_color(
-                canvas,
-                &font_boss.main_font,
-                x,
-                y + precip_height + 70,
-                WEEKDAYS3[weekday as usize],
-                Color::BLACK,
-                0.5,
-            );
+/// PNG of `image` with each widget's box outlined.
+fn outlined_png(
+    image: &Image,
+    outlines: &[(i32, i32, i32, i32)],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut surface = Surface::new_raster_n32_premul((image.width(), image.height()))
+        .ok_or("Failed to create Skia surface")?;
+    let canvas = RenderCanvas::new(surface.canvas());
+    canvas.draw_image(image, (0, 0), None);
+    for &(x, y, width, height) in outlines {
+        draw_rect_thing(&canvas, x, y, width, height);
+    }
+    drop(canvas);
+
+    let data = surface
+        .image_snapshot()
+        .encode_to_data(skia_safe::EncodedImageFormat::PNG)
+        .ok_or("Failed to encode image")?;
+
+    Ok(data.as_bytes().to_vec())
+}
+
+/// Re-reads the config, layout and data and renders the previewed profile
+/// into `state`. Returns the config and layout files to watch, which are
+/// known even when they fail to load.
+fn render_preview(
+    args: &Args,
+    preview: &PreviewArgs,
+    clock: RenderClock,
+    font_boss: &FontBoss,
+    state: &Mutex<PreviewState>,
+) -> Vec<PathBuf> {
+    let started = Instant::now();
+    let mut watched: Vec<PathBuf> = args.config.iter().cloned().collect();
+    let mut status = PreviewStatus::default();
+
+    let result = (|| -> Result<(Vec<u8>, Vec<u8>), Box<dyn std::error::Error>> {
+        let config = match &args.config {
+            Some(path) => load_config(path)?,
+            None => ConfigFile::default(),
+        };
+        let target = preview_target(args, &config, preview.profile.as_deref())?;
+
+        let base_dir = args
+            .config
+            .as_deref()
+            .and_then(FsPath::parent)
+            .unwrap_or(FsPath::new("."));
+        let cli = LayoutDisplay::from_args(args);
+        let (layout_path, overrides) = match target {
+            Some(profile) => (base_dir.join(&profile.layout), profile.display.or(cli)),
+            None => (PathBuf::from("layout.json"), cli),
+        };
+        watched.push(layout_path.clone());
+        status.profile = target
+            .map_or("default", |profile| &profile.name)
+            .to_string();
+        status.layout = layout_path.display().to_string();
+
+        let contents = fs::read_to_string(&layout_path)
+            .map_err(|e| format!("Failed to read {}: {}", layout_path.display(), e))?;
+        status.issues = lint_layout(&contents, &overrides)
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect();
+
+        let profile = match target {
+            Some(profile) => Profile::from_config(profile, base_dir, args)?,
+            None => Profile::from_args(args)?,
+        };
+        let mut data = load_all_data(&args.data_dir, &config.staleness, clock.now());
+        data.battery_profile = profile.name.clone();
+        let frame = render_frame(&profile, font_boss, &data)?;
+
+        Ok((
+            outlined_png(&frame.image, &frame.outlines)?,
+            outlined_png(&panel_view(&profile, &frame)?, &frame.outlines)?,
+        ))
+    })();
+
+    let mut state = state.lock().unwrap();
+    status.version = state.status.version + 1;
+    match result {
+        Ok((raw_png, panel_png)) => {
+            println!("Preview rendered in {:.2?}", started.elapsed());
+            state.raw_png = raw_png;
+            state.panel_png = panel_png;
+            status.rendered_at = Some(Local::now());
+            status.render_ms = Some(started.elapsed().as_millis());
+        }
+        Err(e) => {
+            eprintln!("Preview render failed: {}", e);
+            status.error = Some(e.to_string());
+            status.rendered_at = state.status.rendered_at;
         }
     }
+    state.status = status;
 
-    true
+    watched
 }
 
-pub struct LoadedSvg {
-    image: Image, // Final pre-rendered Skia image
-    pub width: f32,
-    pub height: f32,
+fn handle_preview_request(
+    request: tiny_http::Request,
+    state: &Mutex<PreviewState>,
+) -> io::Result<()> {
+    let path = request.url().split('?').next().unwrap_or("/").to_string();
+
+    let (body, content_type) = {
+        let state = state.lock().unwrap();
+        match path.as_str() {
+            "/" => (PREVIEW_PAGE.as_bytes().to_vec(), "text/html; charset=utf-8"),
+            "/status" => (serde_json::to_vec(&state.status)?, "application/json"),
+            "/raw.png" => (state.raw_png.clone(), "image/png"),
+            "/panel.png" => (state.panel_png.clone(), "image/png"),
+            _ => return request.respond(tiny_http::Response::empty(404)),
+        }
+    };
+
+    // No images until the first good render
+    if body.is_empty() {
+        return request.respond(tiny_http::Response::empty(404));
+    }
+
+    request.respond(
+        tiny_http::Response::from_data(body)
+            .with_header(header("Content-Type", content_type))
+            .with_header(header("Cache-Control", "no-cache")),
+    )
 }
 
-pub fn svg_from_file(
-    path: &str,
-    target_width: u32,
-    target_height: u32,
-    scalar: f32,
-) -> Result<LoadedSvg, Box<dyn std::error::Error>> {
-    let svg_data = std::fs::read(path)?;
+/// Data file mtimes, plus those of the config and layout files.
+fn preview_mtimes(data_dir: &FsPath, watched: &[PathBuf]) -> BTreeMap<PathBuf, SystemTime> {
+    let mut mtimes = input_mtimes(data_dir);
+    for path in watched {
+        if let Ok(modified) = fs::metadata(path).and_then(|m| m.modified()) {
+            mtimes.insert(path.clone(), modified);
+        }
+    }
+    mtimes
+}
 
-    let options = usvg::Options::default();
-    let usvg_tree = usvg::Tree::from_data(&svg_data, &options)?;
-    let resvg_tree = ResvgTree::from_usvg(&usvg_tree);
+/// `preview`: serves a page showing the latest render and re-renders
+/// whenever the config, the layout or a data file changes.
+fn run_preview(
+    args: &Args,
+    preview: &PreviewArgs,
+    clock: RenderClock,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let server = tiny_http::Server::http(&preview.addr)
+        .map_err(|e| format!("Failed to listen on {}: {}", preview.addr, e))?;
+    println!("Previewing on http://{}/", preview.addr);
+
+    let state = Arc::new(Mutex::new(PreviewState::default()));
+    {
+        let state = Arc::clone(&state);
+        std::thread::spawn(move || {
+            for request in server.incoming_requests() {
+                if let Err(e) = handle_preview_request(request, &state) {
+                    eprintln!("HTTP error: {}", e);
+                }
+            }
+        });
+    }
 
-    // Render to full target size
-    let mut pixmap =
-        tiny_skia::Pixmap::new(target_width, target_height).ok_or("Failed to create pixmap")?;
+    let font_boss = FontBoss::new();
+    let poll = Duration::from_millis(preview.poll_ms.max(50));
 
-    let svg_size = resvg_tree.size;
-    let scale_x = target_width as f32 / svg_size.width();
-    let scale_y = target_height as f32 / svg_size.height();
-    let scale = scale_x.min(scale_y) * scalar; // Maintain aspect ratio
+    let mut watched = render_preview(args, preview, clock, &font_boss, &state);
+    let mut seen = preview_mtimes(&args.data_dir, &watched);
 
-    // Calculate translation to center the scaled image
-    let scaled_width = svg_size.width() * scale;
-    let scaled_height = svg_size.height() * scale;
+    loop {
+        std::thread::sleep(poll);
 
-    let transform = tiny_skia::Transform::from_translate(0.0, 0.0).post_scale(scale, scale);
+        if preview_mtimes(&args.data_dir, &watched) != seen {
+            // Give an editor or producer that isn't writing atomically a moment
+            std::thread::sleep(poll);
+            println!("Change detected, re-rendering");
+            watched = render_preview(args, preview, clock, &font_boss, &state);
+            seen = preview_mtimes(&args.data_dir, &watched);
+        }
+    }
+}
 
-    resvg_tree.render(transform, &mut pixmap.as_mut());
+/// ---- Main: read layout.json -> render -> save PNG ----
 
-    // Convert pixmap to skia_safe::Image
-    let image_info = ImageInfo::new(
-        (target_width as i32, target_height as i32),
-        ColorType::RGBA8888,
-        AlphaType::Premul,
-        None,
-    );
+/// Reads `<feed>.json` from `data_dir`, logging rather than failing.
+fn load_feed<T: DeserializeOwned>(
+    data_dir: &FsPath,
+    feed: &str,
+    staleness: &HashMap<String, StalenessPolicy>,
+    now: DateTime<Utc>,
+) -> FeedResult<T> {
+    let path = data_dir.join(format!("{}.json", feed));
 
-    let image = Image::from_raster_data(
-        &image_info,
-        Data::new_copy(pixmap.data()),
-        (target_width * 4) as usize,
-    )
-    .ok_or("Failed to create Skia image")?;
+    load_feed_file(&path, feed, staleness, now)
+}
 
-    Ok(LoadedSvg {
-        image,
-        width: scaled_width as f32,
-        height: scaled_height as f32,
-    })
+/// Like `load_feed`, for feeds whose file isn't named after them.
+fn load_feed_file<T: DeserializeOwned>(
+    path: &FsPath,
+    feed: &str,
+    staleness: &HashMap<String, StalenessPolicy>,
+    now: DateTime<Utc>,
+) -> FeedResult<T> {
+    match absolute_path_string(path).and_then(|path| read_envelope::<T>(&path, now)) {
+        Ok((data, age)) => Ok(Loaded {
+            data: data,
+            status: FeedStatus::new(feed, age, staleness),
+        }),
+        Err(e) => {
+            eprintln!("Failed to load {}: {}", path.display(), e);
+            Err(e.to_string())
+        }
+    }
 }
 
-fn process_verse_token(token: &str) -> (String, bool) {
-    // Check if token matches "[number]" using a simple pattern
-    if let Some(stripped) = token.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
-        // Make sure the inside is numeric
-        if stripped.chars().all(|c| c.is_ascii_digit()) {
-            return (stripped.to_string(), true);
+/// Loads every data feed from `data_dir` into one `AllData` for rendering
+/// the moment `now`. A feed that's missing or malformed only takes out its
+/// own widgets.
+fn load_all_data(
+    data_dir: &FsPath,
+    staleness: &HashMap<String, StalenessPolicy>,
+    now: DateTime<FixedOffset>,
+) -> AllData {
+    let utc = now.to_utc();
+    let weather = load_feed::<WeatherResponse>(data_dir, "weather", staleness, utc);
+    let cleaning = load_feed::<Vec<DailyScore>>(data_dir, "cleaning", staleness, utc);
+    let balances = load_feed::<Vec<PersonBalance>>(data_dir, "balances", staleness, utc);
+    let names = load_feed::<Vec<PersonName>>(data_dir, "names", staleness, utc);
+    let upcoming_payouts =
+        load_feed::<Vec<UpcomingPayout>>(data_dir, "upcoming_payouts", staleness, utc);
+    let todo = load_feed::<Vec<TodoItem>>(data_dir, "todo", staleness, utc);
+
+    // Battery files only exist once a device has reported in, so a missing
+    // one is expected and just means "no report yet".
+    let mut batteries = HashMap::new();
+    for (profile, path) in battery_files(data_dir) {
+        let Ok(battery) = load_feed_file::<BatteryReport>(&path, "battery", staleness, utc) else {
+            continue;
+        };
+        println!(
+            "Battery{} {:.0}%{} ({} V), reported {}",
+            if profile.is_empty() {
+                String::new()
+            } else {
+                format!(" of '{}'", profile)
+            },
+            battery.data.percent,
+            if battery.data.charging {
+                " charging"
+            } else {
+                ""
+            },
+            battery
+                .data
+                .voltage
+                .map(|v| format!("{:.2}", v))
+                .unwrap_or_else(|| "?".to_string()),
+            battery.data.reported_at
+        );
+        batteries.insert(profile, battery);
+    }
+
+    let dates_path = data_dir.join("dates.json");
+    let significant_dates = fs::read_to_string(&dates_path)
+        .map_err(|e| e.to_string())
+        .and_then(|data| {
+            serde_json::from_str::<Vec<SignificantDate>>(&data).map_err(|e| e.to_string())
+        });
+
+    match &significant_dates {
+        Ok(significant_dates) => {
+            for holiday in significant_dates {
+                println!("{} {} on {}", holiday.emoji, holiday.name, holiday.date);
+            }
         }
+        Err(e) => eprintln!("Failed to load {}: {}", dates_path.display(), e),
     }
 
-    // Default: passthrough
-    (token.to_string(), false)
+    AllData {
+        weather: weather,
+        significant_dates: significant_dates,
+        cleaning: cleaning,
+        names: names,
+        balances: balances,
+        upcoming_payouts: upcoming_payouts,
+        todo: todo,
+        batteries: batteries,
+        battery_profile: String::new(),
+        now: now,
+    }
 }
 
-fn measure_and_draw(
-    canvas: &Canvas,
-    font: &Font,
-    ref_font: &Font,
-    x: i32,
-    y: i32,
-    width: i32,
-    height: i32,
-    tokens: &Vec<&str>,
-    attr: &str,
-    draw: bool,
-    ypad: i32,
-) -> f32 {
-    let spacew = font.measure_str(" ", None).0;
-    let padding = 25;
-
-    let raw_lh = line_height(&font) * 0.6;
-    let lh = raw_lh * 1.9;
-    let mut xp = 0.0;
-    let mut yp = raw_lh;
+/// Parses the layout tree and its top-level settings.
+/// Validates the layout against the panel it will be drawn on, then parses
+/// the tree and resolves the panel geometry. Warnings are printed; errors fail.
+fn load_layout(
+    path: &FsPath,
+    overrides: &LayoutDisplay,
+) -> Result<(LayoutNode, PanelConfig), Box<dyn std::error::Error>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    let issues = lint_layout(&contents, overrides);
+    for issue in &issues {
+        println!("{}: {}", path.display(), issue);
+    }
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    if errors > 0 {
+        return Err(format!("{} has {} layout error(s)", path.display(), errors).into());
+    }
 
-    let target_width = (width - padding * 3) as f32;
-    let target_height = (height - padding * 3) as f32;
+    let root: LayoutNode = serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))?;
+    let header: LayoutHeader = serde_json::from_str(&contents)
+        .map_err(|e| format!("Failed to parse {} display settings: {}", path.display(), e))?;
+    let panel = PanelConfig::resolve(overrides, &header.display)?;
 
-    for token in tokens {
-        let (val, is_number) = process_verse_token(token);
-        let token = &val;
+    Ok((root, panel))
+}
 
-        let use_font = if is_number { &ref_font } else { &font };
-        let push_up = if is_number { lh * 0.25 } else { 0.0 } as i32;
-        let color = if is_number {
-            Color::from_rgb(100, 100, 100)
-        } else {
-            Color::BLACK
-        };
+/// `validate-layout`: lints the named files, or every profile's layout.
+fn validate_layouts(
+    args: &Args,
+    config: &ConfigFile,
+    layouts: &[PathBuf],
+) -> Result<(), Box<dyn std::error::Error>> {
+    let cli = LayoutDisplay::from_args(args);
+    let targets: Vec<(PathBuf, LayoutDisplay)> = if !layouts.is_empty() {
+        layouts.iter().map(|path| (path.clone(), cli)).collect()
+    } else if let Some(config_path) = &args.config
+        && !config.profiles.is_empty()
+    {
+        let base_dir = config_path.parent().unwrap_or(FsPath::new("."));
+        config
+            .profiles
+            .iter()
+            .map(|profile| (base_dir.join(&profile.layout), profile.display.or(cli)))
+            .collect()
+    } else {
+        vec![(PathBuf::from("layout.json"), cli)]
+    };
 
-        let ww = use_font.measure_str(token, None).0;
-        // println!("{} - {}", token, ww);
+    let mut errors = 0;
+    for (path, overrides) in &targets {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let issues = lint_layout(&contents, overrides);
 
-        if xp + ww > target_width {
-            xp = 0.0;
-            yp += lh;
+        if issues.is_empty() {
+            println!("{}: OK", path.display());
         }
-
-        if draw {
-            draw_text_blob_with_color(
-                canvas,
-                &use_font,
-                xp as i32 + x + padding,
-                yp as i32 + y + padding + ypad - push_up,
-                token,
-                color,
-                0.0,
-            );
+        for issue in &issues {
+            println!("{}: {}", path.display(), issue);
         }
+        errors += issues
+            .iter()
+            .filter(|issue| issue.severity == Severity::Error)
+            .count();
+    }
 
-        xp += ww;
-        xp += spacew;
+    if errors > 0 {
+        return Err(format!("{} layout error(s)", errors).into());
     }
 
-    yp += lh;
-    xp = 0.0;
+    Ok(())
+}
 
-    if draw {
-        draw_text_blob_with_color(
-            canvas,
-            &font,
-            xp as i32 + x + padding + target_width as i32,
-            yp as i32 + y + padding + ypad,
-            attr,
-            Color::BLACK,
-            1.0,
-        );
-    }
+/// A rendered frame: the Skia image as laid out, plus the dithered level
+/// indices and packed bytes in panel orientation.
+struct Frame {
+    image: Image,
+    levels: Vec<u8>,
+    packed: Vec<u8>,
+    outlines: Vec<(i32, i32, i32, i32)>,
+}
 
-    // draw at      (y + padding) + yp
-    // limit at     (y + padding) as f32 + target_height)
+/// Renders one profile's layout, then dithers and packs it for the panel.
+fn render_frame(
+    profile: &Profile,
+    font_boss: &FontBoss,
+    data: &AllData,
+) -> Result<Frame, Box<dyn std::error::Error>> {
+    let panel = &profile.panel;
+    let root = &profile.root;
 
-    let leftover = target_height - yp;
+    let (canvas_width, canvas_height) = panel.canvas_size();
+    let width = canvas_width as i32;
+    let height = canvas_height as i32;
 
-    // println!("yp {}  th {}", yp, target_height);
+    let mut surface =
+        Surface::new_raster_n32_premul((width, height)).expect("Failed to create Skia surface");
+    let canvas = RenderCanvas::new(surface.canvas());
 
-    leftover
-}
+    // white background
+    canvas.clear(Color::WHITE);
 
-fn draw_verse(canvas: &Canvas, font_boss: &FontBoss, x: i32, y: i32, width: i32, height: i32) {
-    let now = Local::now();
-    let date_str = now.format("%B %d %Y %p").to_string();
-    // let date_str = now.format("%H:%M:%s").to_string();
+    if root.is_container() {
+        handle_child(&canvas, font_boss, root, 0, 0, width, height, data);
+    } else {
+        println!(
+            "Root of {}'s layout must be a container, grid or overlay node.",
+            profile.name
+        );
+    }
 
-    // Hash the string
-    let mut hasher = DefaultHasher::new();
-    date_str.hash(&mut hasher);
-    let hash64 = hasher.finish() as u64;
+    // Optional: draw a paragraph demo in the top-left (uncomment to see)
+    // draw_paragraph_demo(canvas, 60.0, 60.0, 600.0);
 
-    println!("date_str = {date_str}");
+    let dither_methods = canvas.method_map(
+        width as usize,
+        height as usize,
+        profile.dither,
+        profile.text_dither,
+    );
+    let outlines = canvas.outlines.take();
+    drop(canvas);
 
-    // Example: deterministic selection
-    let db_path = "verses.db";
+    // Save to PNG
+    let image = surface.image_snapshot();
 
-    match get_verse_by_seed(db_path, hash64).unwrap() {
-        Some((reference, text)) => {
-            println!("{} → {}", reference, text);
-            really_draw_verse(canvas, font_boss, x, y, width, height, &reference, &text);
+    // Extract red channel
+    let width = image.width() as usize;
+    let height = image.height() as usize;
+    let mut red_channel = Vec::with_capacity(width * height);
+
+    // Read pixels from the image
+    let mut pixels = vec![0u8; width * height * 4]; // RGBA format
+    if image.read_pixels(
+        &skia_safe::ImageInfo::new(
+            (width as i32, height as i32),
+            skia_safe::ColorType::RGBA8888,
+            skia_safe::AlphaType::Unpremul,
+            None,
+        ),
+        &mut pixels,
+        width * 4,
+        (0, 0),
+        CachingHint::Allow,
+    ) {
+        // Extract red channel (every 4th byte starting from index 0)
+        for i in (0..pixels.len()).step_by(4) {
+            red_channel.push(pixels[i]);
         }
-        None => println!("No verses found in DB."),
+    } else {
+        return Err("Failed to read pixels".into());
+    }
+
+    let encoder = profile.output_format.encoder();
+    let red_pixels = if encoder.has_red() {
+        Some(rotate_buffer(
+            &red_mask(&pixels),
+            width,
+            height,
+            panel.rotation,
+        ))
+    } else {
+        None
     };
+
+    // Turn the rendered layout to match how the panel is mounted
+    let red_channel = rotate_buffer(&red_channel, width, height, panel.rotation);
+    let dither_methods = rotate_buffer(&dither_methods, width, height, panel.rotation);
+
+    let darkened = apply_gamma(&red_channel, profile.gamma); // Try 1.3 to 1.8
+
+    let levels = dither_levels(
+        &darkened,
+        &dither_methods,
+        red_pixels.as_deref(),
+        panel.width,
+        panel.height,
+        encoder.as_ref(),
+    );
+    let packed = encoder.pack(&levels, panel.width, panel.height);
+
+    // Verify size
+    let expected_len = encoder.packed_len(panel.width, panel.height);
+    println!(
+        "Packed size: {} bytes (expected {})",
+        packed.len(),
+        expected_len
+    );
+    if packed.len() != expected_len {
+        return Err(format!(
+            "Packed frame is {} bytes but a {}x{} panel needs {} bytes",
+            packed.len(),
+            panel.width,
+            panel.height,
+            expected_len
+        )
+        .into());
+    }
+
+    Ok(Frame {
+        image: image,
+        levels: levels,
+        packed: packed,
+        outlines: outlines,
+    })
 }
 
-fn really_draw_verse(
-    canvas: &Canvas,
+/// Renders one profile's frame and atomically replaces the outputs in its out_dir.
+fn render_and_save(
+    profile: &Profile,
+    delta_tile: usize,
     font_boss: &FontBoss,
-    x: i32,
-    y: i32,
-    width: i32,
-    height: i32,
-    attr: &str,
-    verse: &str,
-) {
-    let margin = 15;
+    data: &AllData,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let panel = &profile.panel;
+    let out_dir = &profile.out_dir;
+    let encoder = profile.output_format.encoder();
 
-    draw_box_with_gradient(
-        canvas,
-        x + margin,
-        y + margin,
-        width - margin,
-        height - margin * 2,
-        Color::from_rgb(230, 230, 230),
-        Color::from_rgb(255, 255, 255),
-    );
+    let Frame { image, packed, .. } = render_frame(profile, font_boss, data)?;
+
+    // ------------------------------------------------------------
+    // 1. Output directory (optional arg, default = cwd)
+    // ------------------------------------------------------------
+    // Ensure output directory exists
+    fs::create_dir_all(out_dir)?;
+
+    let mz_path = out_dir.join("image.mz");
+    let png_path = out_dir.join("output.png");
+    let delta_path = out_dir.join("image.delta.mz");
+    let meta_path = out_dir.join("image.json");
+
+    // Unique-ish temp suffix
+    let pid = std::process::id();
+    let mz_tmp = out_dir.join(format!(".image.mz.tmp.{pid}"));
+    let png_tmp = out_dir.join(format!(".output.png.tmp.{pid}"));
+    let delta_tmp = out_dir.join(format!(".image.delta.mz.tmp.{pid}"));
+    let meta_tmp = out_dir.join(format!(".image.json.tmp.{pid}"));
+
+    println!("mz_tmp {}", mz_tmp.display());
+    println!("png_tmp {}", png_tmp.display());
 
-    let tokens: Vec<&str> = verse.split_whitespace().collect();
+    let compressed = compress_to_vec(&packed, 8);
 
-    for i in (10..=50).rev() {
-        let fsize = i as f32 * 0.5;
+    {
+        let mut file = File::create(&mz_tmp)?;
+        file.write_all(&compressed)?;
+        file.sync_all()?; // ensure bytes hit disk
+    }
 
-        let ref_font = font_boss.load_font(fsize * 0.8);
-        let font = font_boss.load_font(fsize);
+    // Delta against whatever image.mz currently holds
+    let previous = load_previous_frame(out_dir, panel.width, panel.height, profile.output_format);
+    let (delta, delta_rects) = encode_delta(
+        previous.as_deref(),
+        &packed,
+        panel.width,
+        panel.height,
+        encoder.layout(),
+        delta_tile,
+    );
+    match delta_rects {
+        Some(n) => println!("Delta: {} dirty rects, {} bytes", n, delta.len()),
+        None => println!("Delta: full refresh"),
+    }
 
-        let yleftover = measure_and_draw(
-            canvas,
-            &font,
-            &ref_font,
-            x + margin,
-            y + margin,
-            width,
-            height,
-            &tokens,
-            &attr,
-            false,
-            0,
-        );
+    {
+        let mut file = File::create(&delta_tmp)?;
+        file.write_all(&compress_to_vec(&delta, 8))?;
+        file.sync_all()?;
+    }
 
-        let fits = yleftover >= 0.0;
+    let meta = FrameMeta {
+        width: panel.width,
+        height: panel.height,
+        format: profile.output_format,
+        hash: format!("{:016x}", fnv1a_64(&packed)),
+    };
 
-        println!("{fsize} -> {fits}");
+    {
+        let mut file = File::create(&meta_tmp)?;
+        file.write_all(serde_json::to_string_pretty(&meta)?.as_bytes())?;
+        file.sync_all()?;
+    }
 
-        if fits {
-            // Now we can draw!
-            let ypad = (yleftover * 0.5).round();
-            let _ = measure_and_draw(
-                canvas,
-                &font,
-                &ref_font,
-                x + margin,
-                y + margin,
-                width,
-                height,
-                &tokens,
-                &attr,
-                true,
-                ypad as i32,
-            );
+    let data = image
+        .encode_to_data(skia_safe::EncodedImageFormat::PNG)
+        .ok_or("Failed to encode image")?;
 
-            break;
-        }
+    {
+        let file = File::create(&png_tmp)?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(data.as_bytes())?;
+        writer.flush()?;
+        writer.get_ref().sync_all()?;
     }
-}
 
-fn format_cents_commas(cents: i64) -> String {
-    let negative = if cents < 0 { true } else { false };
-    let cents = cents.abs();
+    // ------------------------------------------------------------
+    // 4. Atomic swap
+    // ------------------------------------------------------------
+    // image.json goes last: the server reads its hash as the ETag before
+    // reading the file it serves, so a new ETag always comes with new bytes
+    fs::rename(&delta_tmp, &delta_path)?;
+    fs::rename(&mz_tmp, &mz_path)?;
+    fs::rename(&png_tmp, &png_path)?;
+    fs::rename(&meta_tmp, &meta_path)?;
 
-    let dollars = cents / 100;
-    let remainder = cents % 100;
+    println!(
+        "Saved:\n  {}\n  {}\n  {}",
+        mz_path.display(),
+        delta_path.display(),
+        png_path.display()
+    );
 
-    let s = dollars.to_string();
-    let mut out = String::new();
+    Ok(())
+}
 
-    // Insert commas from the right
-    let mut count = 0;
-    for ch in s.chars().rev() {
-        if count == 3 {
-            out.push(',');
-            count = 0;
-        }
-        out.push(ch);
-        count += 1;
+/// battery.json, shared by every panel, then each panel's own
+/// battery-<profile>.json, keyed by profile name ("" for the shared one).
+fn battery_files(data_dir: &FsPath) -> Vec<(String, PathBuf)> {
+    let mut files = vec![(String::new(), data_dir.join("battery.json"))];
+
+    if let Ok(entries) = fs::read_dir(data_dir) {
+        let mut own: Vec<(String, PathBuf)> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let profile = name.strip_prefix("battery-")?.strip_suffix(".json")?;
+                Some((profile.to_string(), entry.path()))
+            })
+            .collect();
+        own.sort();
+        files.extend(own);
     }
 
-    let dollar_str: String = out.chars().rev().collect();
-    format!(
-        "{}${}.{:02}",
-        if negative { "−" } else { "" },
-        dollar_str,
-        remainder
-    )
+    files
 }
 
-fn today_multiplier(cleaning: &[DailyScore]) -> Option<i32> {
-    let today: NaiveDate = Local::now().date_naive();
-
-    cleaning
+/// Modification times of the feed files `load_all_data` reads. Only those,
+/// so that our own image.json in a shared --out-dir doesn't count as a change.
+fn input_mtimes(data_dir: &FsPath) -> BTreeMap<PathBuf, SystemTime> {
+    AllData::FEEDS
         .iter()
-        .find(|d| NaiveDate::parse_from_str(&d.date, "%Y-%m-%d").ok() == Some(today))
-        .map(|d| d.multiplier)
+        .map(|feed| data_dir.join(format!("{}.json", feed)))
+        .chain(battery_files(data_dir).into_iter().map(|(_, path)| path))
+        .filter_map(|path| {
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            Some((path, modified))
+        })
+        .collect()
 }
 
-fn draw_people(canvas: &Canvas, font_boss: &FontBoss, x: i32, y: i32, width: i32, data: &AllData) {
-    let mini_font = font_boss.load_font(20.0);
-    let mini_rb_font = font_boss.load_roboto_extra_bold_font(20.0);
-    let bold_font = font_boss.load_bold_font(25.0);
+/// The next multiple of `interval` since the epoch, so renders land on the minute.
+fn next_aligned_render(interval: Duration) -> SystemTime {
+    let now = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = interval.as_secs().max(1);
+    let next = (now.as_secs() / secs + 1) * secs;
+    SystemTime::UNIX_EPOCH + Duration::from_secs(next)
+}
 
-    let cleaning = &data.cleaning;
-    let balances = &data.balances;
-    let upcoming_payouts = &data.upcoming_payouts;
-    let names = &data.names;
+/// Keeps fonts, icons and the layout loaded and re-renders on a schedule or
+/// whenever a data file changes. Errors are logged and the loop carries on.
+fn run_daemon(
+    args: &Args,
+    config: &ConfigFile,
+    profiles: &[Profile],
+    clock: RenderClock,
+    font_boss: &FontBoss,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let interval = Duration::from_secs(args.interval);
+    let poll = Duration::from_millis(500);
 
-    ///////////////////////////////////////////////////////////////////////
-    ///////////////////////////////////////////////////////////////////////
+    println!(
+        "Daemon: rendering every {}s and on changes in {}",
+        args.interval,
+        args.data_dir.display()
+    );
 
-    // ---- Build lookup tables ----
-    let balance_by_id: HashMap<u32, &PersonBalance> =
-        balances.iter().map(|b| (b.person_id, b)).collect();
+    let mut seen = input_mtimes(&args.data_dir);
+    let mut next_render = SystemTime::now();
 
-    let mut sums: HashMap<i64, PayoutSum> = HashMap::new();
+    loop {
+        let current = input_mtimes(&args.data_dir);
+        let changed = current != seen;
+        let due = SystemTime::now() >= next_render;
 
-    for payout in upcoming_payouts {
-        let entry = sums.entry(payout.person_id).or_insert(PayoutSum {
-            positive: 0.0,
-            negative: 0.0,
-        });
+        if changed || due {
+            if changed {
+                // Give a producer that isn't writing atomically a moment to finish
+                std::thread::sleep(poll);
+                println!("Data changed, re-rendering");
+            }
+            seen = input_mtimes(&args.data_dir);
 
-        if payout.payout_cents >= 0.0 {
-            entry.positive += payout.payout_cents;
-        } else {
-            entry.negative += payout.payout_cents;
+            let started = Instant::now();
+            let mut data = load_all_data(&args.data_dir, &config.staleness, clock.now());
+            match render_profiles(profiles, args.delta_tile, font_boss, &mut data) {
+                Ok(()) => println!("Rendered in {:.2?}", started.elapsed()),
+                Err(e) => eprintln!("Render failed: {}", e),
+            }
+
+            next_render = next_aligned_render(interval);
         }
-    }
 
-    let name_by_id: HashMap<u32, &str> = names
-        .iter()
-        .map(|n| (n.person_id, n.name.as_str()))
-        .collect();
+        std::thread::sleep(poll);
+    }
+}
 
-    // ---- Dates header ----
-    print!("Dates: ");
-    for day in cleaning {
-        let date = NaiveDate::parse_from_str(&day.date, "%Y-%m-%d").expect("invalid date");
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args = Args::parse();
 
-        let wd = match date.weekday() {
-            chrono::Weekday::Mon => "Mo",
-            chrono::Weekday::Tue => "Tu",
-            chrono::Weekday::Wed => "We",
-            chrono::Weekday::Thu => "Th",
-            chrono::Weekday::Fri => "Fr",
-            chrono::Weekday::Sat => "Sa",
-            chrono::Weekday::Sun => "Su",
-        };
+    let config = match &args.config {
+        Some(path) => load_config(path)?,
+        None => ConfigFile::default(),
+    };
 
-        print!("{}({}) ", wd, day.multiplier);
+    let clock = RenderClock::from_args(&args)?;
+    if let Some(pinned) = clock.pinned {
+        println!("Rendering as of {}", pinned);
     }
-    println!("\n");
 
-    // ---- Collect scores per person, in date order ----
-    let mut scores_by_person: HashMap<u32, Vec<&str>> = HashMap::new();
+    if let Some(Command::ValidateLayout { layouts }) = &args.command {
+        return validate_layouts(&args, &config, layouts);
+    }
 
-    for day in cleaning {
-        for entry in &day.entries {
-            scores_by_person
-                .entry(entry.person_id)
-                .or_default()
-                .push(entry.score.as_str());
-        }
+    if let Some(Command::Preview(preview)) = &args.command {
+        return run_preview(&args, preview, clock);
     }
 
-    ///////////////////////////////////////////////////////////////////////
-    ///////////////////////////////////////////////////////////////////////
+    if let Some(Command::Decode { input, output }) = &args.command {
+        return decode_frame(input, output);
+    }
 
-    let mut xdraw = x + 22;
+    if let Some(Command::Fetch { feed }) = &args.command {
+        return match feed {
+            FetchFeed::Weather(fetch) => {
+                fetch_weather(fetch, config.weather.as_ref(), &args.data_dir)
+            }
+            FetchFeed::All => {
+                let mut names: Vec<String> = config.feeds.keys().cloned().collect();
+                if config.weather.is_some() && !config.feeds.contains_key("weather") {
+                    names.insert(0, "weather".to_string());
+                }
+                fetch_feeds(&names, &config, &args.data_dir)
+            }
+            FetchFeed::Named(names) => fetch_feeds(names, &config, &args.data_dir),
+        };
+    }
 
-    // See if we have a lucky multiplier today
-    if let Some(mult) = today_multiplier(&cleaning)
-        && mult > 1
-    {
-        draw_filled_circle(
-            canvas,
-            Point::new((x + 21) as f32, (y + 8) as f32),
-            23.0,
-            Color::BLACK,
-        );
+    let profiles = load_profiles(&args, &config)?;
 
-        let big_bold_font = font_boss.load_roboto_extra_bold_font(35.0);
-        draw_text_blob_with_color(
-            canvas,
-            &big_bold_font,
-            x + 21,
-            y + 20,
-            &mult.to_string(),
-            Color::WHITE,
-            0.5,
+    if args.delta_tile == 0 || args.delta_tile % 8 != 0 {
+        return Err(format!(
+            "--delta-tile must be a positive multiple of 8, got {}",
+            args.delta_tile
+        )
+        .into());
+    }
+    for profile in &profiles {
+        println!(
+            "Profile '{}': panel {}x{}, rotated {:?}, {:?} output in {}",
+            profile.name,
+            profile.panel.width,
+            profile.panel.height,
+            profile.panel.rotation,
+            profile.output_format,
+            profile.out_dir.display()
         );
-
-        xdraw += 55;
     }
 
-    // If *anybody* got an X or a ? we put up the death circle
-    let yesterday = chrono::Local::now().date_naive() - chrono::Duration::days(1);
-    let yesterday_str = yesterday.format("%Y-%m-%d").to_string();
+    let font_boss = FontBoss::new();
 
-    let forgot_to_clean_yesterday = cleaning
-        .iter()
-        .find(|day| day.date == yesterday_str)
-        .map(|day| {
-            day.entries
+    let server = match &args.serve {
+        Some(addr) => Some(spawn_server(
+            addr,
+            profiles
                 .iter()
-                .any(|entry| entry.score == "❌" || entry.score == "❓")
-        })
-        .unwrap_or(false);
+                .map(|p| (p.name.clone(), p.out_dir.clone()))
+                .collect(),
+            args.data_dir.clone(),
+        )?),
+        None => None,
+    };
 
-    // ...and here it is
-    if forgot_to_clean_yesterday {
-        draw_filled_circle(
-            canvas,
-            Point::new((xdraw) as f32, (y + 8) as f32),
-            23.0,
-            Color::BLACK,
-        );
+    if args.daemon {
+        return run_daemon(&args, &config, &profiles, clock, &font_boss);
+    }
 
-        draw_text_blob_with_color(
-            canvas,
-            &font_boss.emoji_font,
-            xdraw,
-            y + 18,
-            "💀",
-            Color::WHITE,
-            0.5,
-        );
+    // Data is loaded once and shared by every profile
+    let mut data = load_all_data(&args.data_dir, &config.staleness, clock.now());
+    render_profiles(&profiles, args.delta_tile, &font_boss, &mut data)?;
+
+    // A one-shot render keeps serving what it just wrote
+    if let Some(server) = server {
+        server.join().map_err(|_| "HTTP server thread panicked")?;
     }
 
-    let num_dates = cleaning.len().min(5);
-    let mut any_mults = false;
+    Ok(())
+}
 
-    for j in 0..num_dates {
-        if cleaning[j].multiplier > 1 {
-            any_mults = true;
-            break;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift64*, so the property tests are repeatable without a dependency.
+    struct TestRng(u64);
+
+    impl TestRng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 >> 12;
+            self.0 ^= self.0 << 25;
+            self.0 ^= self.0 >> 27;
+            self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
         }
     }
 
-    // weekyday headers
-    for j in 0..num_dates {
-        let day = &cleaning[j];
-        let date = NaiveDate::parse_from_str(&day.date, "%Y-%m-%d").expect("invalid date");
+    #[test]
+    fn pack_then_unpack_round_trips_every_format() {
+        let formats = [
+            OutputFormat::Gray3,
+            OutputFormat::Mono,
+            OutputFormat::Gray2,
+            OutputFormat::Gray4,
+            OutputFormat::Bwr,
+        ];
+        let mut rng = TestRng(0x9e37_79b9_7f4a_7c15);
+
+        for format in formats {
+            let encoder = format.encoder();
+            let levels = encoder.levels() + encoder.has_red() as usize;
+
+            for _ in 0..200 {
+                // Odd widths exercise the padding at the end of each row
+                let width = 1 + rng.below(41);
+                let height = 1 + rng.below(9);
+                let idx: Vec<u8> = (0..width * height)
+                    .map(|_| rng.below(levels) as u8)
+                    .collect();
+
+                let packed = encoder.pack(&idx, width, height);
+                assert_eq!(
+                    packed.len(),
+                    encoder.packed_len(width, height),
+                    "{:?} {}x{}",
+                    format,
+                    width,
+                    height
+                );
+                assert_eq!(
+                    encoder.unpack(&packed, width, height),
+                    idx,
+                    "{:?} {}x{}",
+                    format,
+                    width,
+                    height
+                );
+            }
+        }
+    }
 
-        let wd = match date.weekday() {
-            chrono::Weekday::Mon => "Mo",
-            chrono::Weekday::Tue => "Tu",
-            chrono::Weekday::Wed => "We",
-            chrono::Weekday::Thu => "Th",
-            chrono::Weekday::Fri => "Fr",
-            chrono::Weekday::Sat => "Sa",
-            chrono::Weekday::Sun => "Su",
-        };
+    #[test]
+    fn gray3_keeps_levels_in_the_high_end_of_each_nibble() {
+        let packed = pack_3bpp_high_first(&[7, 1, 4], 3, 1);
+        assert_eq!(packed, vec![0xE2, 0x80]);
+        assert_eq!(unpack_3bpp_high_first(&packed, 3, 1), vec![7, 1, 4]);
+    }
 
-        let opt_mult: Option<i32> = if day.multiplier > 1 {
-            Some(day.multiplier)
-        } else {
-            None
-        }; //Some(2);
+    #[test]
+    fn pinned_now_is_read_in_the_given_zone() {
+        let args = Args::parse_from([
+            "inkhols",
+            "--now",
+            "2026-12-24T07:00",
+            "--tz",
+            "America/Chicago",
+        ]);
+        let clock = RenderClock::from_args(&args).unwrap();
+        assert_eq!(clock.now().to_rfc3339(), "2026-12-24T07:00:00-06:00");
+
+        // 2:30 is skipped when Chicago springs forward
+        let args = Args::parse_from([
+            "inkhols",
+            "--now",
+            "2026-03-08 02:30",
+            "--tz",
+            "America/Chicago",
+        ]);
+        assert!(RenderClock::from_args(&args).is_err());
+    }
+
+    #[test]
+    fn config_without_profiles_renders_the_command_line_profile() {
+        let args = Args::parse_from(["inkhols", "--config", "inkhols.json"]);
+        let file: ConfigFile =
+            serde_json::from_str(r#"{"staleness": {"weather": {"stale_after_hours": 2}}}"#)
+                .unwrap();
+
+        let profiles = load_profiles(&args, &file).unwrap();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].name, "default");
+        assert_eq!(profiles[0].out_dir, args.out_dir);
+        assert!(preview_target(&args, &file, None).unwrap().is_none());
+    }
 
-        draw_text_blob_with_color(
-            canvas,
-            &mini_font,
-            x + width - 205 + 19 + j as i32 * 40,
-            y + 18 - if any_mults { 0 } else { 6 },
-            wd,
-            Color::BLACK,
-            0.5,
+    /// ---- Partial refresh deltas ----
+
+    /// What the device does with an IKD1 file: None when it asks for a full
+    /// refresh, otherwise `prev` with every rect's rows copied in.
+    fn apply_delta(prev: &[u8], delta: &[u8], layout: ByteLayout) -> Option<Vec<u8>> {
+        let u16_at = |at: usize| u16::from_le_bytes([delta[at], delta[at + 1]]) as usize;
+        let u64_at = |at: usize| u64::from_le_bytes(delta[at..at + 8].try_into().unwrap());
+
+        assert_eq!(&delta[..4], b"IKD1");
+        assert_eq!(delta[5] as usize, layout.bits());
+        assert_eq!(delta[6] as usize, layout.planes());
+        if delta[4] == 1 {
+            assert_eq!(u16_at(27), 0);
+            return None;
+        }
+
+        let (width, height) = (u16_at(7), u16_at(9));
+        assert_eq!(u64_at(11), fnv1a_64(prev), "delta is for another frame");
+
+        let row_bytes = layout.row_bytes(width);
+        let plane_len = row_bytes * height;
+        let mut frame = prev.to_vec();
+        let mut at = 29;
+        for _ in 0..u16_at(27) {
+            let (x, y, w, h) = (u16_at(at), u16_at(at + 2), u16_at(at + 4), u16_at(at + 6));
+            at += 8;
+
+            let span = layout.byte_span(x, w);
+            for plane in 0..layout.planes() {
+                for row_y in y..y + h {
+                    let row = plane * plane_len + row_y * row_bytes;
+                    frame[row + span.start..row + span.end]
+                        .copy_from_slice(&delta[at..at + span.len()]);
+                    at += span.len();
+                }
+            }
+        }
+        assert_eq!(at, delta.len(), "trailing bytes in delta");
+        assert_eq!(
+            u64_at(19),
+            fnv1a_64(&frame),
+            "applied frame has the wrong hash"
         );
 
-        if let Some(mult) = opt_mult {
-            draw_filled_circle(
-                canvas,
-                Point::new(
-                    (x + width - 205 + 19 + j as i32 * 40) as f32,
-                    (y - 9) as f32,
-                ),
-                11.0,
-                Color::BLACK,
-            );
+        Some(frame)
+    }
 
-            draw_text_blob_with_color(
-                canvas,
-                &mini_rb_font,
-                x + width - 205 + 19 + j as i32 * 40,
-                y - 2,
-                &mult.to_string(),
-                Color::WHITE,
-                0.5,
+    #[test]
+    fn delta_applied_to_previous_frame_gives_the_new_one() {
+        let mut rng = TestRng(0x5eed);
+        let (width, height) = (100, 70);
+
+        for format in [OutputFormat::Gray3, OutputFormat::Mono, OutputFormat::Bwr] {
+            let encoder = format.encoder();
+            let levels = encoder.levels() + encoder.has_red() as usize;
+            let prev_idx: Vec<u8> = (0..width * height)
+                .map(|_| rng.below(levels) as u8)
+                .collect();
+
+            // A few small scattered changes, including one on the ragged right edge
+            let mut cur_idx = prev_idx.clone();
+            for (x, y) in [(3, 4), (50, 10), (51, 11), (99, 69), (20, 40)] {
+                let i = y * width + x;
+                cur_idx[i] = (cur_idx[i] + 1) % levels as u8;
+            }
+
+            let prev = encoder.pack(&prev_idx, width, height);
+            let cur = encoder.pack(&cur_idx, width, height);
+            let (delta, rects) =
+                encode_delta(Some(&prev), &cur, width, height, encoder.layout(), 8);
+
+            assert!(rects.is_some_and(|n| n > 0), "{:?}", format);
+            assert_eq!(
+                apply_delta(&prev, &delta, encoder.layout()),
+                Some(cur),
+                "{:?}",
+                format
             );
         }
     }
 
-    let mut ordered_names = names.to_vec();
-    ordered_names.sort_by_key(|n| n.person_id);
+    #[test]
+    fn unchanged_frame_gives_an_empty_delta() {
+        let layout = ByteLayout::Packed { bits: 4 };
+        let frame = vec![0x5A; layout.packed_len(64, 32)];
 
-    // ---- Print rows ----
-    let mut i = 0;
-    for person in ordered_names {
-        let scores = scores_by_person.get(&person.person_id);
-        let name = name_by_id.get(&person.person_id).unwrap_or(&"Unknown");
-        let bal = balance_by_id.get(&person.person_id);
-        let upcoming = sums.get(&(person.person_id as i64));
+        let (delta, rects) = encode_delta(Some(&frame), &frame, 64, 32, layout, 32);
+        assert_eq!(rects, Some(0));
+        assert_eq!(apply_delta(&frame, &delta, layout), Some(frame));
+    }
 
-        let yoff = y + i as i32 * 60 + 60;
+    #[test]
+    fn delta_falls_back_to_full_refresh() {
+        let layout = ByteLayout::Packed { bits: 4 };
+        let prev = vec![0x00; layout.packed_len(64, 32)];
+        let cur = vec![0xEE; layout.packed_len(64, 32)];
+
+        // Nothing to diff against
+        let (delta, rects) = encode_delta(None, &cur, 64, 32, layout, 8);
+        assert_eq!(rects, None);
+        assert_eq!(apply_delta(&prev, &delta, layout), None);
+
+        // Most of the panel changed
+        let (delta, rects) = encode_delta(Some(&prev), &cur, 64, 32, layout, 8);
+        assert_eq!(rects, None);
+        assert_eq!(apply_delta(&prev, &delta, layout), None);
+    }
 
-        if let Some(b) = bal {
-            print!("{} -- {}, +{}, {}   ", name, b.balance_cents, 444, 444);
-        } else {
-            print!("{} -- (no balance)   ", name);
+    /// An empty directory under the system temp dir, unique to this test.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("inkhols-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// ---- Daemon change detection ----
+
+    #[test]
+    fn own_outputs_in_the_data_dir_are_not_a_data_change() {
+        // --data-dir and --out-dir both default to "."
+        let dir = temp_dir("mtimes");
+        fs::write(dir.join("weather.json"), "{}").unwrap();
+        let seen = input_mtimes(&dir);
+        assert_eq!(seen.len(), 1);
+
+        for output in [
+            "image.json",
+            "image.mz",
+            "image.delta",
+            "output.png",
+            "notes.json",
+        ] {
+            fs::write(dir.join(output), "x").unwrap();
         }
+        assert_eq!(input_mtimes(&dir), seen);
 
-        // name
-        draw_text_blob(canvas, &font_boss.main_font, x, yoff, &name);
+        fs::write(dir.join("todo.json"), "{}").unwrap();
+        assert_ne!(input_mtimes(&dir), seen);
 
-        if let Some(b) = bal {
-            // balance
-            draw_text_blob_with_color(
-                canvas,
-                &font_boss.main_font,
-                x + width - 240,
-                yoff,
-                &format_cents_commas(b.balance_cents),
-                Color::BLACK,
-                1.0,
-            );
+        fs::remove_dir_all(&dir).unwrap();
+    }
 
-            // weekly up/down
-            if let Some(up) = upcoming {
-                let downcents = (up.negative).round() as i64;
-                let upcents = (up.positive).round() as i64;
+    /// ---- Device reports ----
 
-                let down_balance = if downcents < 0 {
-                    format!("  {}", &format_cents_commas(downcents))
-                } else {
-                    "".to_string()
-                };
+    fn report_params(pairs: &[(&str, &str)]) -> impl Fn(&str, &'static str) -> Option<String> {
+        let pairs: HashMap<String, String> = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key, _header| pairs.get(key).cloned()
+    }
 
-                draw_text_blob_with_color(
-                    canvas,
-                    &mini_font,
-                    x + width - 240,
-                    yoff + 25,
-                    &format!("+{}{}", &format_cents_commas(upcents), &down_balance),
-                    Color::BLACK,
-                    1.0,
-                );
-            }
+    #[test]
+    fn device_report_keeps_fields_a_request_leaves_out() {
+        let first = DateTime::parse_from_rfc3339("2025-03-14T08:00:00Z")
+            .unwrap()
+            .to_utc();
+        let later = first + chrono::Duration::hours(2);
+        let mut report = DeviceReport::default();
+
+        let params = report_params(&[("battery", "64"), ("voltage", "3.91"), ("charging", "0")]);
+        assert!(report.update(params, first));
+
+        // Firmware alone isn't a battery reading
+        assert!(!report.update(report_params(&[("fw", "1.4.2")]), later));
+        assert_eq!(report.battery_percent, Some(64.0));
+        assert_eq!(report.voltage, Some(3.91));
+        assert_eq!(report.charging, Some(false));
+        assert_eq!(report.battery_reported_at, Some(first));
+        assert_eq!(report.firmware.as_deref(), Some("1.4.2"));
+        assert_eq!(report.last_seen, Some(later));
+
+        // A partial reading only replaces what it carries
+        assert!(report.update(report_params(&[("charging", "yes")]), later));
+        assert_eq!(report.battery_percent, Some(64.0));
+        assert_eq!(report.voltage, Some(3.91));
+        assert_eq!(report.charging, Some(true));
+        assert_eq!(report.battery_reported_at, Some(later));
+    }
+
+    #[test]
+    fn each_profile_renders_its_own_battery() {
+        let dir = temp_dir("batteries");
+        let reported = DateTime::parse_from_rfc3339("2025-03-14T13:00:00Z")
+            .unwrap()
+            .to_utc();
+
+        for (profile, percent) in [("kitchen", "80"), ("hall", "12")] {
+            let mut report = DeviceReport::default();
+            report.update(report_params(&[("battery", percent)]), reported);
+            write_battery_envelope(&dir, profile, &report).unwrap();
         }
 
-        // cleaning emojis
-        if let Some(scores) = scores {
-            let today = chrono::Local::now().date_naive();
+        let mut data = load_all_data(&dir, &HashMap::new(), fixture_now());
+        let percent = |data: &AllData| data.battery().map(|b| b.data.percent);
 
-            for k in 0..num_dates.min(num_dates) {
-                let s = &scores[k];
+        data.battery_profile = "kitchen".to_string();
+        assert_eq!(percent(&data), Some(80.0));
+        assert_eq!(data.battery().map(|b| b.data.reported_at), Some(reported));
+        data.battery_profile = "hall".to_string();
+        assert_eq!(percent(&data), Some(12.0));
+        // No report of its own and no shared battery.json
+        data.battery_profile = "attic".to_string();
+        assert_eq!(percent(&data), None);
 
-                // Replace ❓ with ❌ for past dates
-                let display_score = if *s == "❓" {
-                    let date = today - chrono::Duration::days((num_dates - 1 - k) as i64);
-                    if date < today { "❌" } else { s }
-                } else {
-                    s
-                };
+        fs::remove_dir_all(&dir).unwrap();
+    }
 
-                print!("{} ", display_score);
-                draw_text_blob(
-                    canvas,
-                    &font_boss.emoji_font,
-                    x + width - 205 + k as i32 * 40,
-                    yoff + 10,
-                    display_score,
-                );
-            }
-        }
+    /// ---- HTTP request parsing ----
+
+    #[test]
+    fn percent_decode_leaves_malformed_escapes_alone() {
+        assert_eq!(percent_decode("a%20b+c"), "a b c");
+        assert_eq!(percent_decode("%e2%82%ac"), "€");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%4"), "%4");
+        assert_eq!(percent_decode("%zz1"), "%zz1");
+        // Not an escape, so the + is a space as usual
+        assert_eq!(percent_decode("%+1"), "% 1");
+        // Half a UTF-8 sequence decodes lossily rather than failing
+        assert_eq!(percent_decode("%e2%82"), "\u{FFFD}");
+    }
 
-        println!();
-        i += 1;
+    #[test]
+    fn etag_matches_weak_tags_and_lists() {
+        let etag = "\"00ff\"";
+        assert!(etag_matches("\"00ff\"", etag));
+        assert!(etag_matches("W/\"00ff\"", etag));
+        assert!(etag_matches("\"abcd\", W/\"00ff\"", etag));
+        assert!(etag_matches("\"abcd\",\"00ff\"", etag));
+        assert!(etag_matches("*", etag));
+        assert!(!etag_matches("\"abcd\"", etag));
+        assert!(!etag_matches("00ff", etag));
+        assert!(!etag_matches("", etag));
     }
-}
 
-fn maybe_draw_people(
-    canvas: &Canvas,
-    font_boss: &FontBoss,
-    x: i32,
-    y: i32,
-    width: i32,
-    _height: i32,
-    data: &AllData,
-) {
-    if true {
-        draw_people(canvas, font_boss, x, y, width, data);
-    } else {
-        draw_text_blob(canvas, &font_boss.emoji_font, x, y + 20, "😞");
+    #[test]
+    fn route_profile_sends_unknown_prefixes_to_the_first_profile() {
+        let outputs = vec![
+            ("kitchen".to_string(), PathBuf::from("out/kitchen")),
+            ("hall".to_string(), PathBuf::from("out/hall")),
+        ];
 
-        draw_text_blob(
-            canvas,
-            &font_boss.main_font,
-            x + 50,
-            y + 20,
-            "Problem getting allowances",
+        assert_eq!(
+            route_profile("/hall/image.mz", &outputs),
+            ("hall", FsPath::new("out/hall"), "/image.mz")
+        );
+        assert_eq!(
+            route_profile("/image.mz", &outputs),
+            ("kitchen", FsPath::new("out/kitchen"), "/image.mz")
+        );
+        // Unknown names aren't stripped, so they 404 instead of serving a file
+        assert_eq!(
+            route_profile("/attic/image.mz", &outputs),
+            ("kitchen", FsPath::new("out/kitchen"), "/attic/image.mz")
+        );
+        assert_eq!(
+            route_profile("/hall", &outputs),
+            ("kitchen", FsPath::new("out/kitchen"), "/hall")
         );
     }
-}
 
-fn draw_date(canvas: &Canvas, font_boss: &FontBoss, x: i32, y: i32, width: i32, _height: i32) {
-    let font = font_boss.load_font(35.0);
-    let bold_font = font_boss.load_bold_font(35.0);
+    /// ---- Fetchers ----
+
+    /// Serves `responses` in order from a local port, then hands back the
+    /// URLs it was asked for.
+    fn mock_server(
+        responses: Vec<(u16, String)>,
+    ) -> (String, std::thread::JoinHandle<Vec<String>>) {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let addr = server.server_addr().to_ip().unwrap();
+
+        let handle = std::thread::spawn(move || {
+            let mut urls = Vec::new();
+            for (status, body) in responses {
+                let request = server.recv().unwrap();
+                urls.push(request.url().to_string());
+                request
+                    .respond(
+                        tiny_http::Response::from_string(body)
+                            .with_status_code(status)
+                            .with_header(header("Content-Type", "application/json")),
+                    )
+                    .unwrap();
+            }
+            urls
+        });
 
-    // Get the current local datetime
-    let now = Local::now();
+        (format!("http://{}/v1/forecast", addr), handle)
+    }
 
-    // Extract the parts
-    let wday_text = now.format("%A").to_string();
-    let date_text = now.format("%B %d").to_string();
-    let year_text = now.format("%Y").to_string();
+    fn read_envelope_file(path: &FsPath) -> StateEnvelope {
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap()
+    }
 
-    let wday = font.measure_str(&wday_text, None).0;
-    let date = bold_font.measure_str(&date_text, None).0;
-    let year = font.measure_str(&year_text, None).0;
-    let space = font.measure_str(" ", None).0 * 1.5;
+    fn test_weather_location() -> WeatherLocation {
+        WeatherLocation {
+            latitude: 41.88,
+            longitude: -87.63,
+            temperature_unit: default_temperature_unit(),
+            timezone: default_weather_timezone(),
+            policy: FetchPolicy {
+                retries: 0,
+                retry_delay_secs: 0,
+                timeout_secs: 5,
+                expires_minutes: Some(30),
+            },
+        }
+    }
 
-    let lh = line_height(&font);
+    #[test]
+    fn fetch_weather_writes_the_open_meteo_response_as_an_envelope() {
+        let fixture = read_envelope_file(&FsPath::new(FIXTURE_DATA).join("weather.json"));
+        let forecast = fixture.last_good.unwrap().data;
+        let (base_url, server) = mock_server(vec![(200, forecast.to_string())]);
+
+        let dir = temp_dir("fetch-weather");
+        let fetch = WeatherFetchArgs {
+            latitude: None,
+            longitude: None,
+            base_url: base_url,
+        };
+        let before = Utc::now();
+        fetch_weather(&fetch, Some(&test_weather_location()), &dir).unwrap();
+
+        let urls = server.join().unwrap();
+        assert_eq!(urls.len(), 1);
+        let query = parse_query(&urls[0]);
+        assert_eq!(query["latitude"], "41.88");
+        assert_eq!(query["longitude"], "-87.63");
+        assert_eq!(query["hourly"], OPEN_METEO_HOURLY);
+        assert_eq!(query["timezone"], "auto");
+
+        let envelope = read_envelope_file(&dir.join("weather.json"));
+        assert_eq!(envelope.status, "ok");
+        assert_eq!(envelope.error, None);
+        let last_good = envelope.last_good.unwrap();
+        assert_eq!(last_good.data, forecast);
+        assert!(last_good.fetched_at >= before);
+        assert_eq!(
+            last_good.expires,
+            Some(last_good.fetched_at + chrono::Duration::minutes(30))
+        );
 
-    draw_text_blob_with_color(
-        canvas,
-        &font,
-        x + width - 10 - (wday + date + year + space * 3.0) as i32,
-        (y as f32 + lh) as i32 - 2,
-        &wday_text,
-        Color::BLACK,
-        0.0,
-    );
+        fs::remove_dir_all(&dir).unwrap();
+    }
 
-    draw_text_blob_with_color(
-        canvas,
-        &bold_font,
-        x + width - 10 - (date + year + space * 2.0) as i32,
-        (y as f32 + lh) as i32 - 2,
-        &date_text,
-        Color::BLACK,
-        0.0,
-    );
+    #[test]
+    fn fetch_weather_rejects_a_response_that_isnt_a_forecast() {
+        let (base_url, server) = mock_server(vec![(200, r#"{"latitude": 1}"#.to_string())]);
 
-    draw_text_blob_with_color(
-        canvas,
-        &font,
-        x + width - 10 - (year + space * 1.0) as i32,
-        (y as f32 + lh) as i32 - 2,
-        &year_text,
-        Color::BLACK,
-        0.0,
-    );
-}
+        let dir = temp_dir("fetch-weather-bad");
+        let fetch = WeatherFetchArgs {
+            latitude: None,
+            longitude: None,
+            base_url: base_url,
+        };
+        assert!(fetch_weather(&fetch, Some(&test_weather_location()), &dir).is_err());
+        server.join().unwrap();
 
-fn handle_child(
-    canvas: &Canvas,
-    font_boss: &FontBoss,
-    node: &LayoutNode,
-    x: i32,
-    y: i32,
-    width: i32,
-    height: i32,
-    data: &AllData,
-) {
-    match node {
-        LayoutNode::Container(container) => {
-            handle_container(
-                canvas,
-                font_boss,
-                container,
-                &container.split,
-                x,
-                y,
-                width,
-                height,
-                data,
-            );
-        }
-        LayoutNode::Date(_) => {
-            draw_date(canvas, font_boss, x, y, width, height);
+        let envelope = read_envelope_file(&dir.join("weather.json"));
+        assert_eq!(envelope.status, "error");
+        assert!(envelope.error.is_some());
+        assert!(envelope.last_good.is_none());
 
-            // draw_rect_thing(canvas, x, y, width, height);
+        fs::remove_dir_all(&dir).unwrap();
+    }
 
-            // date: render a filled rounded rect and big text
-            // let mut paint = Paint::default();
-            // paint.set_color(Color::from_rgb(240, 240, 240));
-            // paint.set_anti_alias(true);
-            // paint.set_style(PaintStyle::Fill);
+    #[test]
+    fn dates_cant_be_fetched_over_the_hand_edited_file() {
+        let dir = temp_dir("fetch-dates");
+        let source: FeedSource = serde_json::from_str(
+            r#"{"command": ["echo", "[{\"name\": \"Trip\", \"date\": \"2025-06-01\", \"emoji\": \"✈\"}]"]}"#,
+        )
+        .unwrap();
 
-            // let rect = Rect::from_xywh(x as f32 + 4.0, y as f32 + 4.0, width as f32 - 8.0, height as f32 - 8.0);
-            // let rrect = RRect::new_rect_xy(rect, 8.0, 8.0);
-            // canvas.draw_rrect(rrect, &paint);
-        }
-        LayoutNode::Todo(_) => {
-            // draw_rect_thing(canvas, x, y, width, height);
-            // draw_text_blob(canvas, &font_boss.main_font, x, y, "Todo list");
-        }
-        LayoutNode::Weather(_) => {
-            draw_weather_wrapped(
-                canvas,
-                &font_boss,
-                x,
-                y,
-                width,
-                height,
-                &data.weather,
-                data.weather_age_hours,
-            );
-        }
-        LayoutNode::HLine(_) => {
-            // draw_rect_thing(canvas, x, y, width, height);
-            let hbuf = 0.0;
-            let loc = (y as f32 + (y + height) as f32) * 0.5;
-            let start = Point::new(x as f32 + hbuf - 19.0, loc); // Start coordinates
-            let end = Point::new((x + width) as f32 - hbuf, loc); // End coordinates
-            draw_line(canvas, start, end);
-        }
-        LayoutNode::VLine(_) => {
-            // draw_rect_thing(canvas, x, y, width, height);
-            let vbuf = 0.0;
-            let loc = (x as f32 + (x + width) as f32) * 0.5;
-            let start = Point::new(loc, y as f32 + vbuf); // Start coordinates
-            let end = Point::new(loc, (y + height) as f32 - vbuf); // End coordinates
-            draw_line(canvas, start, end);
-        }
-        LayoutNode::Allowance(_) => {
-            maybe_draw_people(canvas, font_boss, x, y, width, height, data);
-        }
-        LayoutNode::Countdown(_) => {
-            let sig_dates = &data.significant_dates;
+        assert!(fetch_feed("dates", &source, &dir).is_err());
+        assert!(!dir.join("dates.json").exists());
+    }
 
-            let mut yoff = y + 20;
-            for i in 0..sig_dates.len() {
-                let target = NaiveDate::parse_from_str(&sig_dates[i].date, "%Y-%m-%d").unwrap();
-                let diff = days_between(target);
+    #[test]
+    fn failed_fetch_keeps_the_last_good_data() {
+        let dir = temp_dir("envelope");
+        let path = dir.join("todo.json");
+        let writer = EnvelopeWriter::new(path.clone(), &test_weather_location().policy);
+        let data = serde_json::json!([{ "title": "Water the plants" }]);
+
+        writer.write(&Ok(data.clone())).unwrap();
+        let ok = read_envelope_file(&path);
+        assert_eq!(ok.status, "ok");
+        let good = ok.last_good.unwrap();
+        assert_eq!(good.fetched_at, ok.fetched_at);
+        assert_eq!(
+            good.expires,
+            Some(good.fetched_at + chrono::Duration::minutes(30))
+        );
 
-                if diff >= 0 {
-                    // draw_rect_thing(canvas, x, y, width, height);
-                    draw_text_blob(
-                        canvas,
-                        &font_boss.emoji_font,
-                        x,
-                        yoff - 2,
-                        &sig_dates[i].emoji,
-                    );
-                    draw_text_blob(
-                        canvas,
-                        &font_boss.main_font,
-                        x + 45,
-                        yoff,
-                        &sig_dates[i].name,
-                    );
-                    draw_text_blob_with_color(
-                        canvas,
-                        &font_boss.main_font,
-                        x + width - 25,
-                        yoff,
-                        &format!("{}", diff),
-                        Color::BLACK,
-                        1.0,
-                    );
+        writer
+            .write(&Err("Request failed: timed out".to_string()))
+            .unwrap();
+        writer
+            .write(&Err("Request failed: refused".to_string()))
+            .unwrap();
+        let failed = read_envelope_file(&path);
+        assert_eq!(failed.status, "error");
+        assert_eq!(failed.error.as_deref(), Some("Request failed: refused"));
+        assert!(failed.fetched_at >= good.fetched_at);
+
+        let kept = failed.last_good.unwrap();
+        assert_eq!(kept.data, data);
+        assert_eq!(kept.fetched_at, good.fetched_at);
+        assert_eq!(kept.expires, good.expires);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 
-                    yoff += 45;
-                }
+    #[test]
+    fn failed_fetch_without_usable_history_has_no_last_good() {
+        let dir = temp_dir("envelope-empty");
+        let path = dir.join("todo.json");
+        let writer = EnvelopeWriter::new(path.clone(), &FetchPolicy::default());
+
+        writer.write(&Err("first try".to_string())).unwrap();
+        assert!(read_envelope_file(&path).last_good.is_none());
+
+        fs::write(&path, "not json").unwrap();
+        writer.write(&Err("second try".to_string())).unwrap();
+        let envelope = read_envelope_file(&path);
+        assert_eq!(envelope.error.as_deref(), Some("second try"));
+        assert!(envelope.last_good.is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fetch_retries_until_it_succeeds_or_runs_out() {
+        let policy = FetchPolicy {
+            retries: 2,
+            retry_delay_secs: 0,
+            ..FetchPolicy::default()
+        };
+
+        let mut calls = 0;
+        let result = fetch_with_retries("test", &policy, || {
+            calls += 1;
+            if calls < 3 {
+                Err(format!("attempt {} failed", calls))
+            } else {
+                Ok(Value::from(calls))
             }
-        }
-        LayoutNode::Battery(_) => {
-            let mini_font = font_boss.load_italic_font(20.0);
-            draw_text_blob_with_color(
-                canvas,
-                &mini_font,
-                x - 5,
-                y,
-                &last_updated_string(),
-                Color::from_rgb(128, 128, 128),
-                0.0,
+        });
+        assert_eq!(result, Ok(Value::from(3)));
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let result = fetch_with_retries("test", &policy, || {
+            calls += 1;
+            Err(format!("attempt {} failed", calls))
+        });
+        assert_eq!(result, Err("attempt 3 failed".to_string()));
+        assert_eq!(calls, 3);
+
+        let mut calls = 0;
+        let result = fetch_with_retries("test", &policy, || {
+            calls += 1;
+            Ok(Value::Null)
+        });
+        assert_eq!(result, Ok(Value::Null));
+        assert_eq!(calls, 1);
+    }
+
+    /// ---- Layout engine ----
+
+    /// A layout node from its layout.json text.
+    fn node(json: &str) -> LayoutNode {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn split(entries: &[&str], align: Align, split_dim_pix: i32) -> (Vec<i32>, Vec<i32>) {
+        let nodes: Vec<LayoutNode> = entries.iter().map(|json| node(json)).collect();
+        let entries: Vec<&LayoutNode> = nodes.iter().collect();
+        split_sizes(&entries, align, split_dim_pix)
+    }
+
+    #[test]
+    fn pinned_children_drop_out_and_the_rest_share_what_is_left() {
+        // 500px left for 4u: the 1u child hits its max, the 2u child its min,
+        // and the last 1u child gets whatever they leave
+        let (starts, sizes) = split(
+            &[
+                r#"{"type": "date", "size": "100px"}"#,
+                r#"{"type": "date", "size": "1u", "max": 50}"#,
+                r#"{"type": "date", "size": "1u"}"#,
+                r#"{"type": "date", "size": "2u", "min": 400}"#,
+            ],
+            Align::Start,
+            600,
+        );
+        assert_eq!(sizes, vec![100, 50, 50, 400]);
+        assert_eq!(starts, vec![0, 100, 150, 200]);
+    }
+
+    #[test]
+    fn percent_children_take_their_share_of_the_container() {
+        let (starts, sizes) = split(
+            &[
+                r#"{"type": "date", "size": "25%"}"#,
+                r#"{"type": "date", "size": "50%", "max": 150}"#,
+                r#"{"type": "date", "size": "1u"}"#,
+                r#"{"type": "date", "size": "10px", "min": 30}"#,
+            ],
+            Align::Start,
+            400,
+        );
+        assert_eq!(sizes, vec![100, 150, 120, 30]);
+        assert_eq!(starts, vec![0, 100, 250, 370]);
+    }
+
+    #[test]
+    fn align_shifts_a_run_that_doesnt_fill_the_container() {
+        let entries = [
+            r#"{"type": "date", "size": "100px"}"#,
+            r#"{"type": "date", "size": "50px"}"#,
+        ];
+        for (align, starts) in [
+            (Align::Start, vec![0, 100]),
+            (Align::Center, vec![75, 175]),
+            (Align::End, vec![150, 250]),
+        ] {
+            assert_eq!(
+                split(&entries, align, 300),
+                (starts, vec![100, 50]),
+                "{:?}",
+                align
             );
         }
-        LayoutNode::Verse(_) => {
-            draw_verse(canvas, font_boss, x, y, width, height);
-        }
+
+        // A run that overflows starts at the top whatever the align
+        let overflowing = [
+            r#"{"type": "date", "size": "200px"}"#,
+            r#"{"type": "date", "size": "200px"}"#,
+        ];
+        assert_eq!(
+            split(&overflowing, Align::End, 300),
+            (vec![0, 200], vec![200, 200])
+        );
     }
-}
 
-fn last_updated_string() -> String {
-    let now = Local::now();
-    now.format("Updated %a %b %-d, %-I:%M %p").to_string()
-}
+    #[test]
+    fn margin_then_padding_inset_the_slot() {
+        let padded =
+            node(r#"{"type": "date", "margin": [10, 20], "padding": {"left": 5, "bottom": 8}}"#);
+        assert_eq!(
+            padded.boxes(100, 50, 300, 200),
+            [(120, 60, 260, 180), (125, 60, 255, 172)]
+        );
 
-/// Read the inner payload of an envelope file and return (payload, hours_old)
-pub fn read_envelope<T: DeserializeOwned>(path: &str) -> io::Result<(T, f64)> {
-    let content = fs::read_to_string(path)?;
-    let envelope: StateEnvelope =
-        serde_json::from_str(&content).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let outdented = node(r#"{"type": "hline", "margin": {"left": -19}}"#);
+        assert_eq!(
+            outdented.boxes(100, 50, 300, 200),
+            [(81, 50, 319, 200), (81, 50, 319, 200)]
+        );
+    }
 
-    let last_good = envelope
-        .last_good
-        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "No last_good data in envelope"))?;
+    /// A 2x2 grid, 310x210 with its 10px gap: the first row is taken by a
+    /// spanning cell and an overlapping one, two children flow into the
+    /// second row, and the last two have nowhere to go.
+    const SPAN_GRID: &str = r#"{
+        "type": "grid",
+        "rows": ["1u", "1u"],
+        "columns": ["100px", "1u"],
+        "gap": 10,
+        "entries": [
+            {"type": "date", "cell": {"row": 0, "column": 0, "column_span": 2}},
+            {"type": "date"},
+            {"type": "date"},
+            {"type": "date"},
+            {"type": "date", "cell": {"row": 1, "column": 2}},
+            {"type": "date", "cell": {"row": 0, "column": 1}}
+        ]
+    }"#;
+
+    #[test]
+    fn grid_places_explicit_cells_first_then_flows_the_rest() {
+        let LayoutNode::Grid(grid) = node(SPAN_GRID) else {
+            panic!("not a grid");
+        };
+        let entries: Vec<&LayoutNode> = grid.entries.iter().collect();
 
-    let payload: T = serde_json::from_value(last_good.data)
-        .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let cell = |row, column, column_span| GridCell {
+            row: row,
+            column: column,
+            row_span: 1,
+            column_span: column_span,
+        };
+        assert_eq!(
+            grid_placements(&grid, &entries),
+            vec![
+                Some(cell(0, 0, 2)),
+                Some(cell(1, 0, 1)),
+                Some(cell(1, 1, 1)),
+                None,
+                None,
+                Some(cell(0, 1, 1)),
+            ]
+        );
+        assert_eq!(
+            grid_rects(&grid, &entries, 310, 210),
+            vec![
+                Some((0, 0, 310, 100)),
+                Some((0, 110, 100, 100)),
+                Some((110, 110, 200, 100)),
+                None,
+                None,
+                Some((110, 0, 200, 100)),
+            ]
+        );
+    }
 
-    let now = Utc::now();
-    let age = now.signed_duration_since(last_good.fetched_at);
-    let hours_old = age.num_seconds() as f64 / 3600.0;
+    #[test]
+    fn check_grid_flags_homeless_off_grid_and_overlapping_children() {
+        let LayoutNode::Grid(grid) = node(SPAN_GRID) else {
+            panic!("not a grid");
+        };
+        let mut lint = LayoutLint {
+            positions: HashMap::new(),
+            issues: Vec::new(),
+        };
+        lint.check_grid(&grid, "$", 310, 210);
+
+        let issues: Vec<(Severity, &str, &str)> = lint
+            .issues
+            .iter()
+            .map(|issue| (issue.severity, issue.path.as_str(), issue.message.as_str()))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                (
+                    Severity::Error,
+                    "$.entries[3]",
+                    "No free cell left in the 2x2 grid"
+                ),
+                (
+                    Severity::Error,
+                    "$.entries[4].cell",
+                    "Cell at row 1, column 2 spanning 1x1 doesn't fit the 2x2 grid"
+                ),
+                (
+                    Severity::Warning,
+                    "$.entries[5].cell",
+                    "Cell overlaps an earlier entry; use an overlay to stack widgets"
+                ),
+            ]
+        );
+    }
 
-    Ok((payload, hours_old))
-}
+    #[test]
+    fn check_grid_counts_gaps_against_fixed_tracks() {
+        let LayoutNode::Grid(grid) = node(
+            r#"{"type": "grid", "rows": ["1u"], "columns": ["200px", "200px"], "gap": 20,
+                "entries": [{"type": "date"}]}"#,
+        ) else {
+            panic!("not a grid");
+        };
+        let mut lint = LayoutLint {
+            positions: HashMap::new(),
+            issues: Vec::new(),
+        };
+        lint.check_grid(&grid, "$", 400, 100);
 
-fn dither_and_pack_3bpp(image_data: &[u8], width: usize, height: usize) -> Vec<u8> {
-    // Step 1: Dither to 8 levels (0-7) using Floyd-Steinberg
-    let dithered = floyd_steinberg_to_levels(image_data, width, height, 8);
+        assert_eq!(lint.issues.len(), 1);
+        assert_eq!(lint.issues[0].path, "$.columns");
+        assert_eq!(
+            lint.issues[0].message,
+            "Fixed columns and gaps need 420px but the grid is only 400px wide"
+        );
+    }
 
-    // Step 2: Pack to 3bpp format
-    pack_3bpp_high_first(&dithered, width, height)
-}
+    #[test]
+    fn overlay_children_shrink_and_sit_at_their_anchor() {
+        for (child, rect) in [
+            (r#"{"type": "date"}"#, (0, 0, 300, 200)),
+            (
+                r#"{"type": "date", "width": "100px", "height": "50%", "anchor": "center"}"#,
+                (100, 50, 100, 100),
+            ),
+            (
+                r#"{"type": "date", "width": "100px", "height": "50%", "anchor": "bottom-right"}"#,
+                (200, 100, 100, 100),
+            ),
+            // Wider than the overlay, so capped to it
+            (
+                r#"{"type": "date", "width": "500px", "height": "40px", "anchor": "right"}"#,
+                (0, 80, 300, 40),
+            ),
+        ] {
+            assert_eq!(overlay_rect(&node(child), 300, 200), rect, "{}", child);
+        }
+    }
 
-fn floyd_steinberg_to_levels(gray: &[u8], width: usize, height: usize, levels: usize) -> Vec<u8> {
-    assert!(levels >= 2, "levels must be >= 2");
+    /// ---- Layout conditions ----
 
-    let mut work = vec![0.0f32; gray.len()];
-    for (i, &val) in gray.iter().enumerate() {
-        work[i] = val as f32;
+    fn condition(json: &str) -> Condition {
+        serde_json::from_str(json).unwrap()
     }
 
-    let mut out = vec![0u8; gray.len()];
-    let step = 255.0 / (levels - 1) as f32;
+    #[test]
+    fn conditions_hold_at_the_times_they_name() {
+        let data = fixture_data();
+        let night = r#"{"time": {"from": "22:00", "to": "06:00"}}"#;
+        let workday = r#"{"time": {"from": "09:00", "to": "17:00"}}"#;
+        let weekend = r#"{"weekdays": ["sat", "sun"]}"#;
+        let holidays = r#"{"dates": {"from": "12-20", "to": "01-05"}}"#;
+        let march = r#"{"dates": {"from": "2025-03-01", "to": "2025-03-14"}}"#;
+        let weekend_or_breakfast = r#"{"any": [
+            {"weekdays": ["sat", "sun"]},
+            {"time": {"from": "08:00", "to": "09:00"}}
+        ]}"#;
+        let not_night = r#"{"not": {"time": {"from": "22:00", "to": "06:00"}}}"#;
+        let weekend_nights = r#"{"time": {"from": "22:00", "to": "06:00"}, "weekdays": ["sat"]}"#;
+
+        // 2025-03-14 is a Friday
+        for (when, now, holds) in [
+            (night, "2025-03-14 23:30", true),
+            (night, "2025-03-14 05:59", true),
+            (night, "2025-03-14 06:00", false),
+            (night, "2025-03-14 12:00", false),
+            (workday, "2025-03-14 09:00", true),
+            (workday, "2025-03-14 17:00", false),
+            (weekend, "2025-03-15 12:00", true),
+            (weekend, "2025-03-14 12:00", false),
+            (holidays, "2025-12-31 12:00", true),
+            (holidays, "2026-01-05 12:00", true),
+            (holidays, "2026-01-06 12:00", false),
+            (holidays, "2025-06-01 12:00", false),
+            (march, "2025-03-14 23:59", true),
+            (march, "2025-03-15 00:00", false),
+            (r#"{"any": []}"#, "2025-03-14 12:00", true),
+            (weekend_or_breakfast, "2025-03-14 08:30", true),
+            (weekend_or_breakfast, "2025-03-15 20:00", true),
+            (weekend_or_breakfast, "2025-03-14 10:00", false),
+            (not_night, "2025-03-14 23:30", false),
+            (not_night, "2025-03-14 12:00", true),
+            (weekend_nights, "2025-03-15 23:00", true),
+            (weekend_nights, "2025-03-14 23:00", false),
+        ] {
+            let at = NaiveDateTime::parse_from_str(now, "%Y-%m-%d %H:%M").unwrap();
+            assert_eq!(
+                condition(when).holds(at, &data),
+                holds,
+                "{} at {}",
+                when,
+                now
+            );
+        }
+    }
 
-    // Floyd-Steinberg kernel: right, bottom-left, bottom, bottom-right
-    // with weights 7/16, 3/16, 5/16, 1/16
-    let kernel = [(1, 0, 7), (-1, 1, 3), (0, 1, 5), (1, 1, 1)];
-    let norm = 16.0;
+    #[test]
+    fn stale_conditions_hold_for_failed_and_old_feeds() {
+        let stale_todo = condition(r#"{"stale": "todo"}"#);
+        let now = fixture_now().naive_local();
 
-    for y in 0..height {
-        // Serpentine scan (zigzag left-right)
-        let (x_start, x_end, x_step) = if y % 2 == 1 {
-            (width - 1, usize::MAX, -1isize)
-        } else {
-            (0, width, 1isize)
-        };
+        let mut data = fixture_data();
+        assert!(!stale_todo.holds(now, &data));
+        data.todo = Err("Failed to read todo.json".to_string());
+        assert!(stale_todo.holds(now, &data));
 
-        let mut x = x_start;
-        while x != x_end {
-            let i = y * width + x;
-            let val = work[i];
-
-            // Quantize to nearest level
-            let k = (val / step).round() as i32;
-            let k = k.clamp(0, (levels - 1) as i32) as u8;
-            out[i] = k;
-
-            // Calculate quantization error
-            let qv = k as f32 * step;
-            let err = val - qv;
-
-            // Diffuse error to neighbors
-            for &(dx, dy, w) in &kernel {
-                let dx_adj = if y % 2 == 1 { -dx } else { dx };
-                let nx = x as isize + dx_adj;
-                let ny = y as isize + dy;
-
-                if nx >= 0 && nx < width as isize && ny >= 0 && ny < height as isize {
-                    let j = (ny as usize) * width + (nx as usize);
-                    work[j] = (work[j] + (err * w as f32) / norm).clamp(0.0, 255.0);
-                }
-            }
+        let later = fixture_now() + chrono::Duration::days(3);
+        let data = load_all_data(FsPath::new(FIXTURE_DATA), &HashMap::new(), later);
+        assert!(stale_todo.holds(later.naive_local(), &data));
+    }
 
-            x = if x_step < 0 { x.wrapping_sub(1) } else { x + 1 };
+    #[test]
+    fn weather_conditions_look_ahead_from_the_pinned_now() {
+        let mut data = fixture_data();
+        let now = data.now.naive_local();
+
+        // Overcast (3) now, rain (61) from 10:00 and clearing (2) before dawn
+        for (when, holds) in [
+            (r#"{"weather": {"codes": [3]}}"#, true),
+            (r#"{"weather": {"codes": [61]}}"#, false),
+            (r#"{"weather": {"codes": [61], "within_hours": 1}}"#, false),
+            (r#"{"weather": {"codes": [61], "within_hours": 2}}"#, true),
+            (r#"{"weather": {"codes": [2], "within_hours": 3}}"#, false),
+        ] {
+            assert_eq!(condition(when).holds(now, &data), holds, "{}", when);
         }
+
+        data.weather = Err("Failed to read weather.json".to_string());
+        assert!(!condition(r#"{"weather": {"codes": [3]}}"#).holds(now, &data));
     }
 
-    out
-}
+    #[test]
+    fn shown_entries_follow_the_render_time() {
+        let LayoutNode::Container(container) = node(
+            r#"{"type": "container", "split": "vertical", "entries": [
+                {"type": "date"},
+                {"type": "todo", "when": {"time": {"from": "05:00", "to": "11:00"}}},
+                {"type": "verse", "when": {"weekdays": ["sat", "sun"]}},
+                {"type": "battery", "when": {"not": {"time": {"from": "05:00", "to": "11:00"}}}}
+            ]}"#,
+        ) else {
+            panic!("not a container");
+        };
+        let shown = |data: &AllData| -> Vec<usize> {
+            shown_entries(&container.entries, data)
+                .into_iter()
+                .map(|shown| {
+                    container
+                        .entries
+                        .iter()
+                        .position(|entry| std::ptr::eq(entry, shown))
+                        .unwrap()
+                })
+                .collect()
+        };
 
-fn pack_3bpp_high_first(idx: &[u8], width: usize, height: usize) -> Vec<u8> {
-    let bytes_per_row = (width + 1) / 2;
-    let mut out = vec![0u8; bytes_per_row * height];
-    let mut oi = 0;
+        let mut data = fixture_data();
+        assert_eq!(shown(&data), vec![0, 1]);
 
-    for y in 0..height {
-        let mut nibs = 0;
-        let mut byte = 0u8;
+        data.now = DateTime::parse_from_rfc3339("2025-03-15T20:00:00-05:00").unwrap();
+        assert_eq!(shown(&data), vec![0, 2, 3]);
+    }
 
-        for x in 0..width {
-            let v3 = idx[y * width + x] & 0x07;
-            let nibble = (v3 << 1) & 0x0F;
-            byte = (byte << 4) | nibble;
-            nibs += 1;
+    /// ---- Layout validation ----
 
-            if nibs == 2 {
-                out[oi] = byte;
-                oi += 1;
-                nibs = 0;
-                byte = 0;
-            }
-        }
+    #[test]
+    fn scanner_records_where_each_value_starts() {
+        let source = "{\n  \"entries\": [\n    {\"size\": \"1u\"},\n    7\n  ]\n}";
+        let lint = LayoutLint {
+            positions: JsonScanner::positions(source),
+            issues: Vec::new(),
+        };
 
-        if nibs == 1 {
-            out[oi] = byte << 4;
-            oi += 1;
+        assert_eq!(lint.positions.len(), 5);
+        for (path, position) in [
+            ("$", (1, 1)),
+            ("$.entries", (2, 14)),
+            ("$.entries[0]", (3, 5)),
+            ("$.entries[0].size", (3, 14)),
+            ("$.entries[1]", (4, 5)),
+            // Paths with no value of their own take their closest parent's
+            ("$.entries[0].margin.left", (3, 5)),
+            ("$.display", (1, 1)),
+        ] {
+            assert_eq!(lint.position_of(path), Some(position), "{}", path);
         }
     }
 
-    out
-}
-
-fn apply_gamma(gray: &[u8], gamma: f32) -> Vec<u8> {
-    gray.iter()
-        .map(|&val| {
-            let normalized = val as f32 / 255.0;
-            let corrected = normalized.powf(gamma);
-            (corrected * 255.0).round() as u8
-        })
-        .collect()
-}
+    #[test]
+    fn broken_layout_reports_each_bad_node_where_it_is() {
+        let source = r#"{
+    "type": "container",
+    "split": "vertical",
+    "entries": [
+        { "type": "date", "size": "32px" },
+        { "type": "clock", "size": "1u" },
+        { "type": "todo" },
+        {
+            "type": "battery",
+            "size": "12 apples",
+            "dither": "sparkly"
+        }
+    ]
+}"#;
+
+        let issues = lint_layout(source, &LayoutDisplay::default());
+        let located: Vec<_> = issues
+            .iter()
+            .map(|issue| (issue.severity, issue.path.as_str(), issue.position))
+            .collect();
+        assert_eq!(
+            located,
+            vec![
+                (Severity::Error, "$.entries[1].type", Some((6, 19))),
+                (Severity::Error, "$.entries[2]", Some((7, 9))),
+                (Severity::Error, "$.entries[3].size", Some((10, 21))),
+                (Severity::Error, "$.entries[3].dither", Some((11, 23))),
+            ]
+        );
+        assert_eq!(
+            issues[1].to_string(),
+            "error at $.entries[2] (line 7, column 9): Missing \"size\""
+        );
+    }
 
-/// Convert any path-like type to a fully qualified absolute path as a String
-fn absolute_path_string<P: AsRef<std::path::Path>>(path: P) -> io::Result<String> {
-    let abs_path = std::fs::canonicalize(path.as_ref())?;
-    abs_path.into_os_string().into_string().map_err(|os_str| {
-        io::Error::new(
-            io::ErrorKind::InvalidData,
-            format!("Failed to convert path to string: {:?}", os_str),
-        )
-    })
-}
+    #[test]
+    fn unparseable_layout_reports_serdes_position() {
+        let source = "{\n  \"type\": \"container\",\n  \"split\" \"vertical\"\n}";
 
-/// ---- Main: read layout.json -> render -> save PNG ----
+        let issues = lint_layout(source, &LayoutDisplay::default());
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].severity, Severity::Error);
+        assert_eq!(issues[0].path, "$");
+        assert_eq!(issues[0].position, Some((3, 11)));
+    }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+    /// ---- Widget data ----
 
-    let cleaning_path = absolute_path_string(args.data_dir.join("cleaning.json"))?;
-    let balances_path = absolute_path_string(args.data_dir.join("balances.json"))?;
-    let names_path = absolute_path_string(args.data_dir.join("names.json"))?;
-    let weather_path = absolute_path_string(args.data_dir.join("weather.json"))?;
-    let dates_path = absolute_path_string(args.data_dir.join("dates.json"))?;
-    let upcoming_payouts_path = absolute_path_string(args.data_dir.join("upcoming_payouts.json"))?;
+    #[test]
+    fn countdown_targets_name_the_entry_with_a_bad_date() {
+        let date = |name: &str, date: &str| SignificantDate {
+            name: name.to_string(),
+            date: date.to_string(),
+            emoji: "🎂".to_string(),
+        };
 
-    let (weather, weather_age_hours) = read_envelope::<WeatherResponse>(&weather_path)?;
-    println!("Weather data is {:.1} hours old", weather_age_hours);
+        let targets = countdown_targets(&[date("Birthday", "2025-06-01")]).unwrap();
+        assert_eq!(targets, vec![NaiveDate::from_ymd_opt(2025, 6, 1).unwrap()]);
 
-    let (cleaning, cleaning_age_hours) = read_envelope::<Vec<DailyScore>>(&cleaning_path)?;
-    println!("People data is {:.1} hours old", cleaning_age_hours);
+        let reason = countdown_targets(&[date("Birthday", "2025-06-01"), date("Trip", "June 3rd")])
+            .unwrap_err();
+        assert!(
+            reason.starts_with("'Trip' has a bad date 'June 3rd'"),
+            "{}",
+            reason
+        );
+    }
 
-    let (balances, balances_age_hours) = read_envelope::<Vec<PersonBalance>>(&balances_path)?;
-    println!("Balances data is {:.1} hours old", balances_age_hours);
+    #[test]
+    fn forecast_times_reject_bad_timestamps_and_ragged_arrays() {
+        let forecast = || match fixture_data().weather {
+            Ok(weather) => weather.data,
+            Err(e) => panic!("{}", e),
+        };
 
-    let (names, names_age_hours) = read_envelope::<Vec<PersonName>>(&names_path)?;
-    println!("Names data is {:.1} hours old", names_age_hours);
+        let weather = forecast();
+        let (hourly, daily) = forecast_times(&weather).unwrap();
+        assert_eq!(hourly.len(), weather.hourly.time.len());
+        assert_eq!(daily.len(), weather.daily.as_ref().unwrap().time.len());
+
+        let mut weather = forecast();
+        weather.hourly.time[3] = "2025-03-14 03:00".to_string();
+        let reason = forecast_times(&weather).unwrap_err();
+        assert!(
+            reason.starts_with("Bad hourly time '2025-03-14 03:00'"),
+            "{}",
+            reason
+        );
 
-    let (upcoming_payouts, upcoming_payouts_age_hours) =
-        read_envelope::<Vec<UpcomingPayout>>(&upcoming_payouts_path)?;
-    println!(
-        "Upcoming_payouts data is {:.1} hours old",
-        upcoming_payouts_age_hours
-    );
+        let mut weather = forecast();
+        weather.hourly.precipitation_probability.pop();
+        let reason = forecast_times(&weather).unwrap_err();
+        assert!(
+            reason.starts_with("Hourly precipitation_probability has"),
+            "{}",
+            reason
+        );
 
-    let data = fs::read_to_string(&dates_path)?;
-    let significant_dates: Vec<SignificantDate> = serde_json::from_str(&data)?;
+        let mut weather = forecast();
+        let daily = weather.daily.as_mut().unwrap();
+        daily.temperature_min.truncate(2);
+        let days = daily.time.len();
+        assert_eq!(
+            forecast_times(&weather).unwrap_err(),
+            format!("Daily temperature_2m_min has 2 entries for {} times", days)
+        );
 
-    for holiday in &significant_dates {
-        println!("{} {} on {}", holiday.emoji, holiday.name, holiday.date);
+        let mut weather = forecast();
+        weather.daily.as_mut().unwrap().time[0] = "Friday".to_string();
+        let reason = forecast_times(&weather).unwrap_err();
+        assert!(reason.starts_with("Bad daily date 'Friday'"), "{}", reason);
     }
 
-    let data = AllData {
-        weather: weather,
-        weather_age_hours: weather_age_hours,
-        significant_dates: significant_dates,
-        cleaning: cleaning,
-        cleaning_age_hours: cleaning_age_hours,
-        names: names,
-        names_age_hours: names_age_hours,
-        balances: balances,
-        balances_age_hours: balances_age_hours,
-        upcoming_payouts: upcoming_payouts,
-        upcoming_payouts_age_hours: upcoming_payouts_age_hours,
-    };
+    /// ---- Golden images: fixture data rendered at a frozen instant ----
 
-    // println!("{:#?}", weather.current.temperature);
+    // Renders are compared against the PNGs in tests/golden. After a
+    // deliberate change, write new goldens with
+    //
+    //     INKHOLS_BLESS=1 cargo test golden
+    //
+    // and review them before committing. A failing comparison leaves
+    // `<name>.actual.png` next to the golden.
 
-    let contents = fs::read_to_string("layout.json")
-        .map_err(|e| format!("Failed to read layout.json: {}", e))?;
-    let root: LayoutNode = serde_json::from_str(&contents)
-        .map_err(|e| format!("Failed to parse layout.json: {}", e))?;
+    const FIXTURE_DATA: &str = "tests/fixtures/data";
+    const GOLDEN_DIR: &str = "tests/golden";
 
-    let width = 1200;
-    let height = 825;
+    /// How far a channel may drift (out of 255) before the pixel counts as
+    /// changed; enough to absorb anti-aliasing differences between Skia builds.
+    const PIXEL_TOLERANCE: u8 = 12;
 
-    let font_boss = FontBoss::new();
+    /// The fixtures are written around this instant: a Friday morning, with
+    /// every feed fresh.
+    fn fixture_now() -> DateTime<FixedOffset> {
+        DateTime::parse_from_rfc3339("2025-03-14T08:30:00-05:00").unwrap()
+    }
 
-    let mut surface =
-        Surface::new_raster_n32_premul((width, height)).expect("Failed to create Skia surface");
-    let canvas = surface.canvas();
+    fn fixture_data() -> AllData {
+        load_all_data(FsPath::new(FIXTURE_DATA), &HashMap::new(), fixture_now())
+    }
 
-    // white background
-    canvas.clear(Color::WHITE);
+    /// The render host's fonts can't be checked in, so public-domain Tuffy
+    /// stands in for every face. Emoji come out as its missing-glyph box.
+    const TEST_FONT_FILES: FontFiles = FontFiles {
+        regular: "tests/fixtures/fonts/Tuffy.ttf",
+        italic: "tests/fixtures/fonts/Tuffy.ttf",
+        bold: "tests/fixtures/fonts/Tuffy.ttf",
+        roboto_extra_bold: "tests/fixtures/fonts/Tuffy.ttf",
+        emoji: "tests/fixtures/fonts/Tuffy.ttf",
+    };
 
-    if let LayoutNode::Container(ref container) = root {
-        handle_container(
-            canvas,
-            &font_boss,
-            container,
-            &container.split,
-            0,
-            0,
-            width,
-            height,
-            &data,
+    fn golden_profile(name: &str, root: LayoutNode, panel: PanelConfig) -> Profile {
+        Profile {
+            name: name.to_string(),
+            root: root,
+            panel: panel,
+            out_dir: PathBuf::from(GOLDEN_DIR),
+            output_format: OutputFormat::Gray3,
+            gamma: 1.6,
+            dither: DitherMethod::FloydSteinberg,
+            text_dither: DitherMethod::None,
+        }
+    }
+
+    fn image_rgba(image: &Image) -> Vec<u8> {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let mut pixels = vec![0u8; width * height * 4];
+        let read = image.read_pixels(
+            &ImageInfo::new(
+                (width as i32, height as i32),
+                ColorType::RGBA8888,
+                AlphaType::Unpremul,
+                None,
+            ),
+            &mut pixels,
+            width * 4,
+            (0, 0),
+            CachingHint::Allow,
         );
-    } else {
-        println!("Root of layout.json must be a container node.");
+        assert!(read, "Failed to read pixels");
+        pixels
     }
 
-    // Optional: draw a paragraph demo in the top-left (uncomment to see)
-    // draw_paragraph_demo(canvas, 60.0, 60.0, 600.0);
+    /// Pixels where any channel differs by more than `PIXEL_TOLERANCE`.
+    fn changed_pixels(expected: &[u8], actual: &[u8]) -> usize {
+        expected
+            .chunks_exact(4)
+            .zip(actual.chunks_exact(4))
+            .filter(|(e, a)| {
+                e.iter()
+                    .zip(*a)
+                    .any(|(&e, &a)| e.abs_diff(a) > PIXEL_TOLERANCE)
+            })
+            .count()
+    }
 
-    // Save to PNG
-    let image = surface.image_snapshot();
+    /// Renders `profile` against the fixtures and compares it to its golden,
+    /// or writes the golden when blessing.
+    fn assert_matches_golden(profile: &Profile) {
+        let font_boss = FontBoss::from_files(&TEST_FONT_FILES);
+
+        let frame = render_frame(profile, &font_boss, &fixture_data()).unwrap();
+        let png = frame
+            .image
+            .encode_to_data(skia_safe::EncodedImageFormat::PNG)
+            .expect("Failed to encode image");
+
+        let golden_path = FsPath::new(GOLDEN_DIR).join(format!("{}.png", profile.name));
+        let actual_path = FsPath::new(GOLDEN_DIR).join(format!("{}.actual.png", profile.name));
+
+        if std::env::var_os("INKHOLS_BLESS").is_some() {
+            fs::create_dir_all(GOLDEN_DIR).unwrap();
+            fs::write(&golden_path, png.as_bytes()).unwrap();
+            let _ = fs::remove_file(&actual_path);
+            println!("Blessed {}", golden_path.display());
+            return;
+        }
 
-    // Extract red channel
-    let width = image.width() as usize;
-    let height = image.height() as usize;
-    let mut red_channel = Vec::with_capacity(width * height);
+        let Ok(golden_png) = fs::read(&golden_path) else {
+            panic!(
+                "No golden at {}; run INKHOLS_BLESS=1 cargo test golden to create it",
+                golden_path.display()
+            );
+        };
+        let golden = Image::from_encoded(Data::new_copy(&golden_png))
+            .unwrap_or_else(|| panic!("{} isn't a readable PNG", golden_path.display()));
 
-    // Read pixels from the image
-    let mut pixels = vec![0u8; width * height * 4]; // RGBA format
-    if image.read_pixels(
-        &skia_safe::ImageInfo::new(
-            (width as i32, height as i32),
-            skia_safe::ColorType::RGBA8888,
-            skia_safe::AlphaType::Unpremul,
-            None,
-        ),
-        &mut pixels,
-        width * 4,
-        (0, 0),
-        CachingHint::Allow,
-    ) {
-        // Extract red channel (every 4th byte starting from index 0)
-        for i in (0..pixels.len()).step_by(4) {
-            red_channel.push(pixels[i]);
+        let size = (frame.image.width(), frame.image.height());
+        let golden_size = (golden.width(), golden.height());
+        let changed = if size == golden_size {
+            changed_pixels(&image_rgba(&golden), &image_rgba(&frame.image))
+        } else {
+            usize::MAX
+        };
+
+        if changed > 0 {
+            fs::write(&actual_path, png.as_bytes()).unwrap();
+            if size != golden_size {
+                panic!(
+                    "{} rendered at {:?}, but its golden is {:?}; see {}",
+                    profile.name,
+                    size,
+                    golden_size,
+                    actual_path.display()
+                );
+            }
+            panic!(
+                "{} differs from its golden in {} pixels; see {}",
+                profile.name,
+                changed,
+                actual_path.display()
+            );
         }
-    } else {
-        return Err("Failed to read pixels".into());
+        let _ = fs::remove_file(&actual_path);
     }
 
-    let darkened = apply_gamma(&red_channel, 1.6); // Try 1.3 to 1.8
+    /// One widget filling a panel of its own.
+    fn assert_widget_matches_golden(node_type: &str, width: usize, height: usize) {
+        let root: LayoutNode = serde_json::from_value(serde_json::json!({
+            "type": "container",
+            "split": "vertical",
+            "entries": [{ "type": node_type }]
+        }))
+        .unwrap();
+        let panel = PanelConfig {
+            width: width,
+            height: height,
+            rotation: Rotation::R0,
+        };
 
-    let packed = dither_and_pack_3bpp(&darkened, width, height);
+        assert_matches_golden(&golden_profile(
+            &format!("widget-{}", node_type),
+            root,
+            panel,
+        ));
+    }
 
-    // Verify size
-    println!("Packed size: {} bytes (expected 495000)", packed.len());
-    assert_eq!(packed.len(), 495_000, "Size mismatch!");
+    #[test]
+    fn fixtures_load_fresh_and_valid() {
+        let data = fixture_data();
 
-    // ------------------------------------------------------------
-    // 1. Output directory (optional arg, default = cwd)
-    // ------------------------------------------------------------
-    // Ensure output directory exists
-    fs::create_dir_all(&args.out_dir)?;
+        for feed in AllData::FEEDS {
+            if feed == "dates" {
+                continue;
+            }
+            let status = data
+                .feed_status(feed)
+                .unwrap_or_else(|| panic!("{} fixture didn't load", feed));
+            assert_eq!(status.staleness, Staleness::Fresh, "{}", feed);
+        }
+        assert!(data.significant_dates.is_ok());
+
+        let (Ok(cleaning), Ok(names), Ok(balances), Ok(upcoming_payouts)) = (
+            &data.cleaning,
+            &data.names,
+            &data.balances,
+            &data.upcoming_payouts,
+        ) else {
+            unreachable!();
+        };
+        assert_eq!(
+            validate_allowance_data(
+                &cleaning.data,
+                &names.data,
+                &balances.data,
+                &upcoming_payouts.data,
+                data.now.date_naive(),
+            ),
+            Ok(())
+        );
+    }
 
-    let mz_path = args.out_dir.join("image.mz");
-    let png_path = args.out_dir.join("output.png");
+    #[test]
+    fn forecast_times_are_read_in_the_forecast_zone() {
+        // A UTC render host, with the fixture forecast for Chicago
+        let now = DateTime::parse_from_rfc3339("2025-03-14T13:30:00+00:00").unwrap();
+        let data = load_all_data(FsPath::new(FIXTURE_DATA), &HashMap::new(), now);
 
-    // Unique-ish temp suffix
-    let pid = std::process::id();
-    let mz_tmp = args.out_dir.join(format!(".image.mz.tmp.{pid}"));
-    let png_tmp = args.out_dir.join(format!(".output.png.tmp.{pid}"));
+        assert_eq!(
+            data.local_now(ZoneSource::Weather).to_string(),
+            "2025-03-14 08:30:00"
+        );
+        assert_eq!(
+            data.local_now(ZoneSource::Render).to_string(),
+            "2025-03-14 13:30:00"
+        );
+    }
 
-    println!("mz_tmp {}", mz_tmp.display());
-    println!("png_tmp {}", png_tmp.display());
+    #[test]
+    fn golden_full_layout() {
+        let (root, panel) =
+            load_layout(FsPath::new("layout.json"), &LayoutDisplay::default()).unwrap();
+        assert_matches_golden(&golden_profile("layout", root, panel));
+    }
 
-    let compressed = compress_to_vec(&packed, 8);
+    #[test]
+    fn golden_date() {
+        assert_widget_matches_golden("date", 500, 40);
+    }
 
-    {
-        let mut file = File::create(&mz_tmp)?;
-        file.write_all(&compressed)?;
-        file.sync_all()?; // ensure bytes hit disk
+    #[test]
+    fn golden_weather() {
+        assert_widget_matches_golden("weather", 720, 565);
     }
 
-    let data = image
-        .encode_to_data(skia_safe::EncodedImageFormat::PNG)
-        .ok_or("Failed to encode image")?;
+    #[test]
+    fn golden_verse() {
+        assert_widget_matches_golden("verse", 720, 260);
+    }
 
-    {
-        let file = File::create(&png_tmp)?;
-        let mut writer = BufWriter::new(file);
-        writer.write_all(data.as_bytes())?;
-        writer.flush()?;
-        writer.get_ref().sync_all()?;
+    #[test]
+    fn golden_allowance() {
+        assert_widget_matches_golden("allowance", 480, 205);
     }
 
-    // ------------------------------------------------------------
-    // 4. Atomic swap
-    // ------------------------------------------------------------
-    fs::rename(&mz_tmp, &mz_path)?;
-    fs::rename(&png_tmp, &png_path)?;
+    #[test]
+    fn golden_countdown() {
+        assert_widget_matches_golden("countdown", 480, 200);
+    }
+
+    #[test]
+    fn golden_todo() {
+        assert_widget_matches_golden("todo", 480, 150);
+    }
 
-    println!("Saved:\n  {}\n  {}", mz_path.display(), png_path.display());
+    #[test]
+    fn golden_battery() {
+        assert_widget_matches_golden("battery", 480, 24);
+    }
 
-    Ok(())
+    #[test]
+    fn golden_lines() {
+        assert_widget_matches_golden("hline", 480, 50);
+        assert_widget_matches_golden("vline", 40, 300);
+    }
 }